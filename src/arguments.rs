@@ -5,6 +5,10 @@ use clap::{ArgEnum, Parser, Subcommand};
 #[derive(Debug, Parser)]
 #[clap(author, version, about, verbatim_doc_comment, long_about = None)]
 pub struct Args {
+    /// Don't page long output through `$PAGER`
+    #[clap(long, global = true)]
+    pub no_pager: bool,
+
     /// Subcommand.
     #[clap(subcommand)]
     pub command: SubCommands,
@@ -12,6 +16,21 @@ pub struct Args {
 
 #[derive(Debug, Subcommand)]
 pub enum SubCommands {
+    /// Show how long services took to start and the critical chain
+    Blame(SubCommandBlame),
+
+    /// Check services for problems
+    Check(SubCommandCheck),
+
+    /// Show the dependency tree for services
+    Deps(SubCommandDeps),
+
+    /// Export the dependency graph (DOT, Mermaid or JSON)
+    Graph(SubCommandGraph),
+
+    /// Show which services are affected if a service stops or restarts
+    Impact(SubCommandImpact),
+
     /// List services
     List(SubCommandList),
 
@@ -19,9 +38,30 @@ pub enum SubCommands {
     #[clap(alias = "logs")]
     Log(SubCommandLog),
 
+    /// Show milestones and what is blocking them
+    Milestones(SubCommandMilestones),
+
+    /// List processes for services
+    Ps(SubCommandPs),
+
     /// Get service status
     Status(SubCommandStatus),
 
+    /// Show live resource usage for services
+    Top(SubCommandTop),
+
+    /// Wait for services to reach a state
+    Wait(SubCommandWait),
+
+    /// Find the service that owns a process
+    Which(SubCommandWhich),
+
+    /// Explain why services are offline
+    WhyOffline(SubCommandWhyOffline),
+
+    /// Signal all processes in service contracts
+    Kill(SubCommandKill),
+
     /// Enable service(s).
     Enable { services: Vec<String> },
 
@@ -40,7 +80,7 @@ pub struct SubCommandList {
     #[clap(short, long)]
     pub contract: bool,
 
-    /// Show `ptree` output for services with a contract
+    /// Show the process tree for services with a contract
     #[clap(short, long)]
     pub tree: bool,
 
@@ -60,27 +100,286 @@ pub struct SubCommandList {
 
 /// `smf status ...`
 #[derive(Debug, Parser)]
+#[clap(after_help = "EXIT STATUS:
+    0    All services are online
+    1    An error occurred
+    3    Some services are not online
+    4    Some patterns matched no services
+    5    Some services are in maintenance")]
 pub struct SubCommandStatus {
     /// Show more output (include multiline values)
     #[clap(short, long)]
     pub long: bool,
 
+    /// Don't print anything (only set the exit status)
+    #[clap(short, long, conflicts_with = "long")]
+    pub quiet: bool,
+
+    /// Services to process
+    #[clap(required = true)]
+    pub services: Vec<String>,
+}
+
+/// `smf ps ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandPs {
     /// Services to process
     #[clap(required = true)]
     pub services: Vec<String>,
 }
 
+/// `smf top ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandTop {
+    /// Seconds to wait between refreshes
+    #[clap(
+        short,
+        long,
+        default_value = "2",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub interval: u64,
+
+    /// Exit after refreshing this many times
+    #[clap(short, long)]
+    pub count: Option<u32>,
+
+    /// Only show the top N services
+    #[clap(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Sort output based on the given field
+    #[clap(short, long, value_enum, default_value = "cpu")]
+    pub sort: TopSortItems,
+
+    /// Services to process (defaults to all services)
+    pub services: Vec<String>,
+}
+
+/// `smf wait ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandWait {
+    /// State to wait for
+    #[clap(short, long, value_enum, default_value = "online")]
+    pub state: WaitState,
+
+    /// Give up after this many seconds (0 waits forever)
+    #[clap(short, long, default_value = "300")]
+    pub timeout: u64,
+
+    /// Seconds to wait between checks
+    #[clap(
+        short,
+        long,
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub interval: u64,
+
+    /// Services to process
+    #[clap(required = true)]
+    pub services: Vec<String>,
+}
+
+/// `smf which ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandWhich {
+    /// Show more output (include multiline values)
+    #[clap(short, long)]
+    pub long: bool,
+
+    /// Process IDs to look up
+    #[clap(required = true)]
+    pub pids: Vec<u32>,
+}
+
+/// `smf why-offline ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandWhyOffline {
+    /// Services to process (defaults to all offline services)
+    pub services: Vec<String>,
+}
+
+/// `smf kill ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandKill {
+    /// Signal to send (name or number)
+    #[clap(short, long, default_value = "TERM")]
+    pub signal: String,
+
+    /// Only list the processes that would be signaled
+    #[clap(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Don't ask for confirmation when multiple services match
+    #[clap(short, long)]
+    pub yes: bool,
+
+    /// Services to process
+    #[clap(required = true)]
+    pub services: Vec<String>,
+}
+
+/// `smf blame ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandBlame {
+    /// Only show the N slowest services
+    #[clap(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Milestone to show the critical chain for
+    #[clap(short, long, default_value = "multi-user")]
+    pub milestone: String,
+}
+
+/// `smf check ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandCheck {
+    #[clap(subcommand)]
+    pub command: CheckCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CheckCommands {
+    /// Check the dependency graph for cycles and broken dependencies
+    Deps,
+}
+
+/// `smf deps ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandDeps {
+    /// Show dependents (services that depend on these) instead
+    #[clap(short, long)]
+    pub reverse: bool,
+
+    /// How many levels of the tree to show (defaults to all)
+    #[clap(short, long)]
+    pub depth: Option<usize>,
+
+    /// Services to process
+    #[clap(required = true)]
+    pub services: Vec<String>,
+}
+
+/// `smf graph ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandGraph {
+    /// Output format
+    #[clap(short = 'o', long, value_enum, default_value = "dot")]
+    pub format: GraphFormat,
+
+    /// Include dependents of the services instead of their dependencies
+    #[clap(short, long, requires = "services")]
+    pub reverse: bool,
+
+    /// Services to include (defaults to all)
+    pub services: Vec<String>,
+}
+
+/// `smf impact ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandImpact {
+    /// Show the impact of restarting the services instead of stopping them
+    #[clap(short, long)]
+    pub restart: bool,
+
+    /// Services to process
+    #[clap(required = true)]
+    pub services: Vec<String>,
+}
+
+/// `smf milestones ...`
+#[derive(Debug, Parser)]
+pub struct SubCommandMilestones {
+    /// List the members of every milestone and every blocking service
+    #[clap(short, long)]
+    pub long: bool,
+}
+
 /// `smf log ...`
 #[derive(Debug, Parser)]
 pub struct SubCommandLog {
-    /// Follow the log file (passes `-F` to `tail`)
+    /// Follow the log file (like `tail -F`)
     #[clap(short, long)]
     pub follow: bool,
 
-    /// Number of lines to view (passes `-n` to `tail`)
+    /// Number of lines to view (defaults to 10)
     #[clap(short, long)]
     pub number: Option<u32>,
 
+    /// Show everything logged since the service was last started
+    #[clap(short = 'r', long, conflicts_with = "number")]
+    pub since_restart: bool,
+
+    /// Only show lines logged at or after this time (ie. `1h`, `2023-10-09`)
+    #[clap(short = 'S', long, conflicts_with_all = &["number", "since-restart"])]
+    pub since: Option<String>,
+
+    /// Only show lines logged at or before this time (ie. `5m`, `12:00`)
+    #[clap(short = 'U', long, conflicts_with = "number")]
+    pub until: Option<String>,
+
+    /// Interleave lines from all services, prefixed with the service name
+    #[clap(short, long)]
+    pub merge: bool,
+
+    /// How to format JSON (bunyan) log lines
+    #[clap(short = 'o', long, value_enum, default_value = "auto")]
+    pub format: LogFormat,
+
+    /// Only show bunyan records at or above this level
+    #[clap(short, long, value_enum)]
+    pub level: Option<LogLevel>,
+
+    /// Only show lines logged by the restarter (start, stop, exit, etc.)
+    #[clap(short, long)]
+    pub events_only: bool,
+
+    /// Search the log files for lines matching this regex
+    #[clap(
+        short,
+        long,
+        conflicts_with_all = &[
+            "follow",
+            "merge",
+            "number",
+            "since-restart",
+            "since",
+            "until",
+            "format",
+            "level",
+            "events-only",
+        ]
+    )]
+    pub grep: Option<String>,
+
+    /// Lines of context to show around each match
+    #[clap(short = 'C', long, requires = "grep", default_value = "0")]
+    pub context: usize,
+
+    /// List the log files (with sizes and modified times) instead of tailing
+    #[clap(
+        short = 'L',
+        long,
+        conflicts_with_all = &[
+            "follow",
+            "merge",
+            "grep",
+            "number",
+            "since-restart",
+            "since",
+            "until",
+            "format",
+            "level",
+            "events-only",
+        ]
+    )]
+    pub list: bool,
+
+    /// Include rotated (and gzipped) log files from before the last rotation
+    #[clap(short = 'R', long)]
+    pub rotated: bool,
+
     /// Services to process
     #[clap(required = true)]
     pub services: Vec<String>,
@@ -95,6 +394,54 @@ pub enum ListSortItems {
     Contract,
 }
 
+#[derive(Debug, Clone, ArgEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum TopSortItems {
+    Cpu,
+    Mem,
+    Rss,
+    Procs,
+    Fmri,
+}
+
+#[derive(Debug, Clone, ArgEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+#[derive(Debug, Clone, ArgEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum LogFormat {
+    Auto,
+    Raw,
+    Bunyan,
+    Json,
+}
+
+#[derive(Debug, Clone, ArgEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+#[derive(Debug, Clone, ArgEnum)]
+#[clap(rename_all = "lowercase")]
+pub enum WaitState {
+    Online,
+    Offline,
+    Degraded,
+    Maintenance,
+    Disabled,
+}
+
 pub fn parse() -> Args {
     Args::parse()
 }
@@ -0,0 +1,318 @@
+//! `smf blame ...`
+
+use std::cmp::Reverse;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDateTime};
+use colored::*;
+
+use crate::util;
+use util::color_aware_string::ColorAwareString;
+use util::deps::{is_running, Graph, Grouping, Service};
+use util::smf::stylize_smf_fmri;
+
+use crate::arguments::SubCommandBlame;
+
+/// How long a service took to come online.
+struct Startup<'a> {
+    svc: &'a Service,
+    /// When the service came online.
+    online: NaiveDateTime,
+    /// The dependency the service waited on the longest (if any).
+    waited_on: Option<&'a Service>,
+    /// The time between `waited_on` (or boot) coming online and the service.
+    took: Duration,
+}
+
+pub fn run(cmd: SubCommandBlame) -> Result<()> {
+    let graph = Graph::load().context("failed to load dependency graph")?;
+
+    // the first service to change state is as close to boot as we can get
+    let boot = match graph.services().filter_map(|svc| svc.state_time).min() {
+        Some(boot) => boot,
+        None => bail!("no service state times found"),
+    };
+
+    // services that came online after the milestone were restarted since
+    // (and didn't hold up booting)
+    let milestone = find_milestone(&graph, &cmd.milestone)?;
+    let until = milestone.state_time.filter(|_| is_running(&milestone.state));
+
+    let mut restarted = 0;
+    let mut startups = vec![];
+    for s in graph.services().filter_map(|svc| startup(&graph, svc, &boot)) {
+        match until.is_none_or(|until| s.online <= until) {
+            true => startups.push(s),
+            false => restarted += 1,
+        }
+    }
+    startups.sort_by_key(|s| Reverse(s.took));
+
+    let limit = cmd.limit.unwrap_or(startups.len());
+    for s in startups.iter().take(limit) {
+        let time = ColorAwareString::with_string(stylize_duration(&s.took));
+        println!("{}  {}", time.pad_start(12), fmri_name(&s.svc.fmri));
+    }
+    if restarted > 0 {
+        println!(
+            "{}",
+            format!(
+                "({} service(s) restarted since boot not shown)",
+                restarted
+            )
+            .black()
+            .bold()
+        );
+    }
+
+    println!();
+    println!("{}", "critical chain:".bold());
+    print_critical_chain(&graph, &milestone.fmri, &boot)
+}
+
+/// Find a milestone by name (ie. `multi-user`) or FMRI.
+fn find_milestone<'a>(graph: &'a Graph, name: &str) -> Result<&'a Service> {
+    let fmri = match name.starts_with("svc:/") {
+        true => name.to_string(),
+        false => format!("svc:/milestone/{}", name),
+    };
+
+    match graph.resolve(&fmri).first() {
+        Some(svc) => Ok(svc),
+        None => bail!("milestone not found: {}", fmri),
+    }
+}
+
+/**
+ * Print the chain of services `fmri` waited on (the dependency that came
+ * online last at every step) like `systemd-analyze critical-chain`:
+ *
+ * ```text
+ * svc:/milestone/multi-user:default @1m 5.02s +10ms
+ * └─ svc:/site/app:default @1m 5.01s +45.2s
+ *    └─ svc:/network/physical:default @19.8s +12.41s
+ * ```
+ */
+fn print_critical_chain(
+    graph: &Graph,
+    fmri: &str,
+    boot: &NaiveDateTime,
+) -> Result<()> {
+    let mut cur = match graph.get(fmri) {
+        Some(svc) => svc,
+        None => bail!("service not found: {}", fmri),
+    };
+
+    let mut indent = String::new();
+    let mut seen = vec![];
+    loop {
+        let s = match startup(graph, cur, boot) {
+            Some(s) => s,
+            None => {
+                println!("{}{} (not online)", indent, fmri_name(&cur.fmri));
+                break;
+            }
+        };
+
+        let at = s.online - *boot;
+        println!(
+            "{}{} {} +{}",
+            indent,
+            fmri_name(&cur.fmri),
+            format!("@{}", format_duration(&at)).black().bold(),
+            stylize_duration(&s.took)
+        );
+
+        seen.push(&cur.fmri);
+        match s.waited_on {
+            Some(dep) if !seen.contains(&&dep.fmri) => cur = dep,
+            _ => break,
+        }
+
+        if indent.is_empty() {
+            indent = "└─ ".to_string();
+        } else {
+            indent = format!("   {}", indent);
+        }
+    }
+
+    Ok(())
+}
+
+/// Figure out how long a running service took to come online after the
+/// dependencies it needed.
+fn startup<'a>(
+    graph: &'a Graph,
+    svc: &'a Service,
+    boot: &NaiveDateTime,
+) -> Option<Startup<'a>> {
+    if !is_running(&svc.state) {
+        return None;
+    }
+    let online = svc.state_time?;
+
+    // the dependencies that had to be online first
+    let mut needed: Vec<&Service> = vec![];
+    for group in &svc.deps {
+        let deps = group
+            .targets
+            .iter()
+            .filter(|target| !target.is_file())
+            .flat_map(|target| graph.resolve(&target.fmri))
+            .filter(|dep| is_running(&dep.state) && dep.state_time.is_some());
+
+        match group.grouping {
+            Grouping::RequireAll | Grouping::OptionalAll => needed.extend(deps),
+            // only the first service to come online was needed
+            Grouping::RequireAny => {
+                needed.extend(deps.min_by_key(|dep| dep.state_time))
+            }
+            Grouping::ExcludeAll => (),
+        }
+    }
+
+    // dependencies that changed state since (ie. restarted) weren't waited on
+    let waited_on = needed
+        .into_iter()
+        .filter(|dep| dep.state_time.is_some_and(|t| t <= online))
+        .max_by_key(|dep| dep.state_time);
+
+    let since = waited_on.and_then(|dep| dep.state_time).unwrap_or(*boot);
+    let took = online - since;
+
+    Some(Startup { svc, online, waited_on, took })
+}
+
+/// Format a duration as `1h 2m 3.45s` (or `120ms` if under a second).
+fn format_duration(d: &Duration) -> String {
+    let millis = d.num_milliseconds();
+    if millis < 1000 {
+        return format!("{}ms", millis);
+    }
+
+    let secs = d.num_seconds();
+    let (hours, minutes) = (secs / 3600, secs / 60 % 60);
+
+    // the hundredths of a second (without trailing zeros)
+    let seconds = format!("{}.{:02}", secs % 60, millis % 1000 / 10);
+    let seconds = seconds.trim_end_matches('0').trim_end_matches('.');
+
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m {}s", minutes, seconds),
+        _ => format!("{}h {}m {}s", hours, minutes, seconds),
+    }
+}
+
+fn stylize_duration(d: &Duration) -> String {
+    let s = format_duration(d);
+
+    match d.num_seconds() {
+        n if n >= 60 => s.red().to_string(),
+        n if n >= 10 => s.yellow().to_string(),
+        _ => s,
+    }
+}
+
+fn fmri_name(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVCPROP_OUTPUT: &str = "\
+svc:/a:default/:properties/restarter/state astring online
+svc:/a:default/:properties/restarter/state_timestamp time 100.000000000
+svc:/b:default/:properties/restarter/state astring online
+svc:/b:default/:properties/restarter/state_timestamp time 101.500000000
+svc:/b:default/:properties/a/grouping astring require_all
+svc:/b:default/:properties/a/restart_on astring none
+svc:/b:default/:properties/a/entities fmri svc:/a
+svc:/c:default/:properties/restarter/state astring online
+svc:/c:default/:properties/restarter/state_timestamp time 105.000000000
+svc:/c:default/:properties/any/grouping astring require_any
+svc:/c:default/:properties/any/restart_on astring none
+svc:/c:default/:properties/any/entities fmri svc:/b svc:/a
+svc:/c:default/:properties/d/grouping astring require_all
+svc:/c:default/:properties/d/restart_on astring none
+svc:/c:default/:properties/d/entities fmri svc:/d:default
+svc:/d:default/:properties/restarter/state astring online
+svc:/d:default/:properties/restarter/state_timestamp time 300.000000000
+svc:/e:default/:properties/restarter/state astring offline
+svc:/e:default/:properties/restarter/state_timestamp time 102.000000000
+svc:/milestone/multi-user:default/:properties/restarter/state astring online
+svc:/milestone/multi-user:default/:properties/restarter/state_timestamp time 106.000000000
+";
+
+    #[test]
+    fn test_startup() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        let boot = parse_time(90_000);
+
+        let startup = |fmri| {
+            let svc = graph.get(fmri).unwrap();
+            let s = startup(&graph, svc, &boot)?;
+            Some((s.waited_on.map(|dep| dep.fmri.as_str()), s.took))
+        };
+
+        assert_eq!(startup("svc:/a:default"), Some((None, millis(10_000))));
+        assert_eq!(
+            startup("svc:/b:default"),
+            Some((Some("svc:/a:default"), millis(1500)))
+        );
+
+        // only the first `require_any` dependency online was waited on, and
+        // `svc:/d:default` restarted since
+        assert_eq!(
+            startup("svc:/c:default"),
+            Some((Some("svc:/a:default"), millis(5000)))
+        );
+
+        assert_eq!(startup("svc:/e:default"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let tests = [
+            (0, "0ms"),
+            (120, "120ms"),
+            (1000, "1s"),
+            (1500, "1.5s"),
+            (12_345, "12.34s"),
+            (60_000, "1m 0s"),
+            (61_230, "1m 1.23s"),
+            (3_723_450, "1h 2m 3.45s"),
+        ];
+
+        for (ms, expected) in tests {
+            assert_eq!(format_duration(&millis(ms)), expected);
+        }
+    }
+
+    #[test]
+    fn test_find_milestone() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+
+        let fmri = |name| find_milestone(&graph, name).map(|svc| &svc.fmri);
+        assert_eq!(
+            fmri("multi-user").unwrap(),
+            "svc:/milestone/multi-user:default"
+        );
+        assert_eq!(
+            fmri("svc:/milestone/multi-user:default").unwrap(),
+            "svc:/milestone/multi-user:default"
+        );
+        assert!(fmri("single-user").is_err());
+        assert!(fmri("svc:/milestone/none").is_err());
+    }
+
+    fn parse_time(ms: i64) -> NaiveDateTime {
+        NaiveDateTime::default() + millis(ms)
+    }
+
+    fn millis(ms: i64) -> Duration {
+        Duration::milliseconds(ms)
+    }
+}
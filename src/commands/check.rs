@@ -0,0 +1,274 @@
+//! `smf check ...`
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+
+use crate::util;
+use util::deps::{DepGroup, Graph, Grouping, Service};
+use util::smf::stylize_smf_fmri;
+
+use crate::arguments::{CheckCommands, SubCommandCheck};
+
+/// A problem found in the dependency graph.
+enum Finding<'a> {
+    /// Services that (indirectly) depend on themselves.
+    Cycle(&'a [String]),
+    /// A required dependency that doesn't exist.
+    Missing(&'a Service, &'a DepGroup, &'a str),
+    /// An enabled service requiring a disabled one.
+    Disabled(&'a Service, &'a DepGroup, &'a str),
+    /// Both sides of an `exclude_all` dependency are enabled.
+    Excluded(&'a Service, &'a DepGroup, &'a str),
+    /// Properties of a service (or `svcprop` output) that couldn't be parsed.
+    Invalid(Option<&'a Service>, &'a str),
+}
+
+impl Finding<'_> {
+    fn format(&self) -> String {
+        let (kind, s) = match self {
+            Finding::Cycle(fmris) => {
+                let mut names: Vec<_> = fmris.iter().map(|f| fmri(f)).collect();
+                names.push(fmri(&fmris[0]));
+                ("cycle", names.join(&" -> ".black().bold().to_string()))
+            }
+            Finding::Missing(svc, group, dep) => {
+                ("missing", format_dependency(svc, group, dep))
+            }
+            Finding::Disabled(svc, group, dep) => {
+                ("disabled", format_dependency(svc, group, dep))
+            }
+            Finding::Excluded(svc, group, dep) => {
+                ("conflict", format_dependency(svc, group, dep))
+            }
+            Finding::Invalid(Some(svc), problem) => {
+                ("invalid", format!("{} {}", fmri(&svc.fmri), problem))
+            }
+            Finding::Invalid(None, line) => {
+                ("invalid", format!("svcprop line: {}", line))
+            }
+        };
+
+        format!("{} {:<8} {}", "✖".red(), kind.red().bold(), s)
+    }
+}
+
+pub fn run(cmd: SubCommandCheck) -> Result<()> {
+    match cmd.command {
+        CheckCommands::Deps => check_deps(),
+    }
+}
+
+/// Check the dependency graph of every service for problems.
+fn check_deps() -> Result<()> {
+    // invalid properties are reported as findings instead of warnings
+    let graph = Graph::read().context("failed to load dependency graph")?;
+
+    let cycles = graph.find_cycles();
+    let mut findings: Vec<_> =
+        cycles.iter().map(|cycle| Finding::Cycle(cycle)).collect();
+
+    for line in &graph.invalid {
+        findings.push(Finding::Invalid(None, line));
+    }
+
+    for svc in graph.services() {
+        for problem in &svc.invalid {
+            findings.push(Finding::Invalid(Some(svc), problem));
+        }
+        for group in &svc.deps {
+            findings.extend(check_group(&graph, svc, group));
+        }
+    }
+
+    for finding in &findings {
+        println!("{}", finding.format());
+    }
+
+    if !findings.is_empty() {
+        bail!("found {} dependency problem(s)", findings.len());
+    }
+
+    println!("{} no dependency problems found", "✔".green());
+
+    Ok(())
+}
+
+/// Check a single dependency group of a service.
+fn check_group<'a>(
+    graph: &'a Graph,
+    svc: &'a Service,
+    group: &'a DepGroup,
+) -> Vec<Finding<'a>> {
+    let mut findings = vec![];
+
+    // resolve every service in the group (files are never checked)
+    let targets: Vec<_> = group
+        .targets
+        .iter()
+        .filter(|target| !target.is_file())
+        .map(|target| (target.fmri.as_str(), graph.resolve(&target.fmri)))
+        .collect();
+    if targets.is_empty() {
+        return findings;
+    }
+
+    match group.grouping {
+        // any instance of a service is enough to satisfy it
+        Grouping::RequireAll => {
+            for (fmri, resolved) in &targets {
+                if resolved.is_empty() {
+                    findings.push(Finding::Missing(svc, group, fmri));
+                } else if svc.enabled && resolved.iter().all(|d| !d.enabled) {
+                    findings.push(Finding::Disabled(svc, group, fmri));
+                }
+            }
+        }
+        // only a problem if none of the services can satisfy it
+        Grouping::RequireAny => {
+            let deps: Vec<_> = targets.iter().flat_map(|(_, r)| r).collect();
+            if deps.is_empty() {
+                for (fmri, _) in &targets {
+                    findings.push(Finding::Missing(svc, group, fmri));
+                }
+            } else if svc.enabled && deps.iter().all(|dep| !dep.enabled) {
+                for dep in deps {
+                    findings.push(Finding::Disabled(svc, group, &dep.fmri));
+                }
+            }
+        }
+        // missing or disabled optional dependencies are fine
+        Grouping::OptionalAll => (),
+        Grouping::ExcludeAll => {
+            let deps = targets.iter().flat_map(|(_, r)| r);
+            for dep in deps.filter(|dep| dep.enabled) {
+                if svc.enabled {
+                    findings.push(Finding::Excluded(svc, group, &dep.fmri));
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+fn fmri(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+fn format_dependency(svc: &Service, group: &DepGroup, dep: &str) -> String {
+    format!(
+        "{} {} {}",
+        fmri(&svc.fmri),
+        group.label().black().bold(),
+        fmri(dep)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVCPROP_OUTPUT: &str = "\
+svc:/on:default/:properties/general/enabled boolean true
+svc:/off:default/:properties/general/enabled boolean false
+svc:/some:a/:properties/general/enabled boolean true
+svc:/some:b/:properties/general/enabled boolean false
+svc:/none:a/:properties/general/enabled boolean false
+svc:/none:b/:properties/general/enabled boolean false
+svc:/app:default/:properties/general/enabled boolean true
+";
+
+    #[test]
+    fn test_check_group() {
+        // the dependency group of `svc:/app:default` with the findings for it
+        let tests: [(&str, &str, &[&str]); 14] = [
+            ("require_all", "svc:/on", &[]),
+            ("require_all", "svc:/off", &["disabled svc:/off"]),
+            ("require_all", "svc:/off:default", &["disabled svc:/off:default"]),
+            ("require_all", "svc:/gone", &["missing svc:/gone"]),
+            ("require_all", "svc:/some", &[]),
+            ("require_all", "svc:/some:b", &["disabled svc:/some:b"]),
+            ("require_all", "svc:/none", &["disabled svc:/none"]),
+            ("require_all", "file://localhost/gone", &[]),
+            ("require_any", "svc:/off svc:/on", &[]),
+            (
+                "require_any",
+                "svc:/off svc:/none",
+                &[
+                    "disabled svc:/off:default",
+                    "disabled svc:/none:a",
+                    "disabled svc:/none:b",
+                ],
+            ),
+            ("require_any", "svc:/gone", &["missing svc:/gone"]),
+            ("optional_all", "svc:/off svc:/gone", &[]),
+            ("exclude_all", "svc:/off svc:/gone", &[]),
+            ("exclude_all", "svc:/some", &["conflict svc:/some:a"]),
+        ];
+
+        for (grouping, entities, want) in tests {
+            let pg = "svc:/app:default/:properties/dep";
+            let s = format!(
+                "{}{pg}/grouping astring {}\n\
+                 {pg}/restart_on astring none\n\
+                 {pg}/entities fmri {}\n",
+                SVCPROP_OUTPUT,
+                grouping,
+                entities,
+                pg = pg
+            );
+            let graph = Graph::parse(&s).unwrap();
+            let svc = graph.get("svc:/app:default").unwrap();
+
+            let found: Vec<_> = check_group(&graph, svc, &svc.deps[0])
+                .iter()
+                .map(|finding| match finding {
+                    Finding::Missing(_, _, dep) => format!("missing {}", dep),
+                    Finding::Disabled(_, _, dep) => format!("disabled {}", dep),
+                    Finding::Excluded(_, _, dep) => format!("conflict {}", dep),
+                    _ => unreachable!(),
+                })
+                .collect();
+            assert_eq!(found, want, "{} {}", grouping, entities);
+        }
+
+        // disabled services don't need their dependencies
+        let graph = Graph::parse(
+            "\
+svc:/off:default/:properties/general/enabled boolean false
+svc:/off:default/:properties/dep/grouping astring require_all
+svc:/off:default/:properties/dep/restart_on astring none
+svc:/off:default/:properties/dep/entities fmri svc:/off
+",
+        )
+        .unwrap();
+        let svc = graph.get("svc:/off:default").unwrap();
+        assert!(check_group(&graph, svc, &svc.deps[0]).is_empty());
+    }
+
+    #[test]
+    fn test_cycle_findings() {
+        let graph = Graph::parse(
+            "\
+svc:/a:default/:properties/dep/grouping astring require_all
+svc:/a:default/:properties/dep/restart_on astring none
+svc:/a:default/:properties/dep/entities fmri svc:/b
+svc:/b:default/:properties/dep/grouping astring optional_all
+svc:/b:default/:properties/dep/restart_on astring none
+svc:/b:default/:properties/dep/entities fmri svc:/a:default
+",
+        )
+        .unwrap();
+
+        // optional dependencies still order startup so they count
+        let cycles = graph.find_cycles();
+        let findings: Vec<_> =
+            cycles.iter().map(|cycle| Finding::Cycle(cycle)).collect();
+        match findings[..] {
+            [Finding::Cycle(fmris)] => {
+                assert_eq!(fmris, ["svc:/a:default", "svc:/b:default"])
+            }
+            _ => panic!("expected a single cycle"),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! `smf deps ...`
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use colored::*;
+use smf::{Query, SmfState};
+
+use crate::util;
+use util::deps::{parse_state, DepGroup, Graph};
+use util::smf::{
+    get_status_matching, stylize_smf_fmri, stylize_smf_state_small,
+};
+
+use crate::arguments::SubCommandDeps;
+
+/// A child in the tree and the dependency group connecting it to its parent.
+struct Edge<'a> {
+    fmri: String,
+    state: Option<&'a SmfState>,
+    /// Used for dependencies that aren't services (ie. `file://`, `absent`).
+    raw_state: &'a str,
+    group: &'a DepGroup,
+}
+
+struct TreePrinter<'a> {
+    graph: &'a Graph,
+    reverse: bool,
+    depth: Option<usize>,
+    /// Services whose children have already been printed.
+    seen: HashSet<String>,
+}
+
+impl<'a> TreePrinter<'a> {
+    /// Get the children of a service (dependencies or dependents).
+    fn children(&self, fmri: &str) -> Vec<Edge<'a>> {
+        let mut edges = vec![];
+
+        if self.reverse {
+            for (svc, group) in self.graph.dependents(fmri) {
+                edges.push(Edge {
+                    fmri: svc.fmri.clone(),
+                    state: Some(&svc.state),
+                    raw_state: "",
+                    group,
+                });
+            }
+            return edges;
+        }
+
+        let svc = match self.graph.get(fmri) {
+            Some(svc) => svc,
+            None => return edges,
+        };
+
+        for group in &svc.deps {
+            for target in &group.targets {
+                let resolved = match target.is_file() {
+                    true => vec![],
+                    false => self.graph.resolve(&target.fmri),
+                };
+
+                // files and missing services are shown as-is
+                if resolved.is_empty() {
+                    edges.push(Edge {
+                        fmri: target.fmri.clone(),
+                        state: None,
+                        raw_state: &target.state,
+                        group,
+                    });
+                }
+
+                for dep in resolved {
+                    edges.push(Edge {
+                        fmri: dep.fmri.clone(),
+                        state: Some(&dep.state),
+                        raw_state: "",
+                        group,
+                    });
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Print the children of `fmri` (recursively) below it.
+    fn print_children(&mut self, fmri: &str, indent: &str, level: usize) {
+        if self.depth.is_some_and(|depth| level >= depth) {
+            return;
+        }
+        if !self.seen.insert(fmri.to_string()) {
+            return;
+        }
+
+        let children = self.children(fmri);
+        let len = children.len();
+        for (i, edge) in children.into_iter().enumerate() {
+            let last = i + 1 == len;
+            let (branch, next_indent) = match last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+
+            // don't print the same subtree twice
+            let repeated = self.seen.contains(&edge.fmri)
+                && !self.children(&edge.fmri).is_empty();
+
+            let mut line = format!(
+                "{}{}{} {}",
+                indent,
+                branch.black().bold(),
+                format_node(&edge.fmri, edge.state, edge.raw_state),
+                edge.group.label().black().bold()
+            );
+            if repeated {
+                line = format!("{} {}", line, "(see above)".black().bold());
+            }
+            println!("{}", line);
+
+            if !repeated {
+                let indent =
+                    format!("{}{}", indent, next_indent.black().bold());
+                self.print_children(&edge.fmri, &indent, level + 1);
+            }
+        }
+    }
+}
+
+pub fn run(cmd: SubCommandDeps) -> Result<()> {
+    let q = Query::new();
+    let (svcs, _) = get_status_matching(&q, &cmd.services)?;
+
+    let graph = Graph::load().context("failed to load dependency graph")?;
+
+    let mut printer = TreePrinter {
+        graph: &graph,
+        reverse: cmd.reverse,
+        depth: cmd.depth,
+        seen: HashSet::new(),
+    };
+
+    for (i, svc) in svcs.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        println!("{}", format_node(&svc.fmri, Some(&svc.state), ""));
+
+        // every tree is printed in full
+        printer.seen.clear();
+        printer.print_children(&svc.fmri, "", 0);
+    }
+
+    Ok(())
+}
+
+/// Format a node as its state char and FMRI.
+fn format_node(
+    fmri: &str,
+    state: Option<&SmfState>,
+    raw_state: &str,
+) -> String {
+    let name = stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string());
+
+    let parsed = parse_state(raw_state);
+    match state.or(parsed.as_ref()) {
+        Some(state) => format!("{} {}", stylize_smf_state_small(state), name),
+        None => format!("{} {} ({})", "?".red().bold(), name, raw_state.red()),
+    }
+}
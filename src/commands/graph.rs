@@ -0,0 +1,413 @@
+//! `smf graph ...`
+
+use std::collections::VecDeque;
+
+use anyhow::{Context, Result};
+use indexmap::map::IndexMap;
+use serde_json::json;
+use smf::Query;
+
+use crate::util;
+use util::deps::{state_name, DepGroup, Graph, Grouping};
+use util::smf::get_status_matching;
+
+use crate::arguments::{GraphFormat, SubCommandGraph};
+
+/// A dependency from one node to another.
+struct Edge<'a> {
+    from: usize,
+    to: usize,
+    group: &'a DepGroup,
+}
+
+/// The nodes (FMRI -> state) and edges to output.
+#[derive(Default)]
+struct Output<'a> {
+    nodes: IndexMap<String, String>,
+    edges: Vec<Edge<'a>>,
+}
+
+impl<'a> Output<'a> {
+    /// Get the index of a node (adding it if it doesn't exist yet).
+    fn node(&mut self, fmri: &str, state: &str) -> usize {
+        match self.nodes.get_index_of(fmri) {
+            Some(i) => i,
+            None => self.nodes.insert_full(fmri.into(), state.into()).0,
+        }
+    }
+
+    /**
+     * Add a service and the edges to its dependencies.
+     *
+     * If `only` is given dependencies not in it are skipped, otherwise every
+     * dependency (including files and missing services) is added.
+     */
+    fn add_service(
+        &mut self,
+        graph: &'a Graph,
+        fmri: &str,
+        only: Option<&[String]>,
+    ) {
+        let svc = match graph.get(fmri) {
+            Some(svc) => svc,
+            None => return,
+        };
+        let from = self.node(&svc.fmri, state_name(&svc.state));
+
+        for group in &svc.deps {
+            for target in &group.targets {
+                let resolved = match target.is_file() {
+                    true => vec![],
+                    false => graph.resolve(&target.fmri),
+                };
+
+                if resolved.is_empty() && only.is_none() {
+                    let to = self.node(&target.fmri, &target.state);
+                    self.edges.push(Edge { from, to, group });
+                }
+
+                for dep in resolved {
+                    if only.is_some_and(|only| !only.contains(&dep.fmri)) {
+                        continue;
+                    }
+                    let to = self.node(&dep.fmri, state_name(&dep.state));
+                    self.edges.push(Edge { from, to, group });
+                }
+            }
+        }
+    }
+}
+
+pub fn run(cmd: SubCommandGraph) -> Result<()> {
+    let graph = Graph::load().context("failed to load dependency graph")?;
+    let mut output = Output::default();
+
+    if cmd.services.is_empty() {
+        for svc in graph.services() {
+            output.add_service(&graph, &svc.fmri, None);
+        }
+    } else {
+        let (svcs, _) = get_status_matching(&Query::new(), &cmd.services)?;
+
+        let fmris = walk(
+            &graph,
+            svcs.into_iter().map(|svc| svc.fmri).collect(),
+            cmd.reverse,
+        );
+
+        // dependencies of dependents may be outside of the subgraph
+        let only = match cmd.reverse {
+            true => Some(fmris.as_slice()),
+            false => None,
+        };
+        for fmri in &fmris {
+            output.add_service(&graph, fmri, only);
+        }
+    }
+
+    let s = match cmd.format {
+        GraphFormat::Dot => format_dot(&output),
+        GraphFormat::Mermaid => format_mermaid(&output),
+        GraphFormat::Json => format_json(&output)?,
+    };
+    println!("{}", s);
+
+    Ok(())
+}
+
+/// Find every service reachable from the given ones by following
+/// dependencies (or dependents if `reverse` is set).
+fn walk(graph: &Graph, fmris: Vec<String>, reverse: bool) -> Vec<String> {
+    let mut todo = VecDeque::from(fmris);
+    let mut found: Vec<String> = vec![];
+
+    while let Some(fmri) = todo.pop_front() {
+        if found.contains(&fmri) {
+            continue;
+        }
+
+        if reverse {
+            let dependents = graph.dependents(&fmri);
+            todo.extend(dependents.iter().map(|(svc, _)| svc.fmri.clone()));
+        } else if let Some(svc) = graph.get(&fmri) {
+            for group in &svc.deps {
+                for target in &group.targets {
+                    let resolved = graph.resolve(&target.fmri);
+                    todo.extend(resolved.iter().map(|dep| dep.fmri.clone()));
+                }
+            }
+        }
+
+        found.push(fmri);
+    }
+
+    found
+}
+
+/// Graphviz fill color for a state.
+fn dot_color(state: &str) -> &'static str {
+    match state {
+        "online" => "palegreen",
+        "offline" | "uninitialized" => "khaki",
+        "degraded" => "orange",
+        "maintenance" => "salmon",
+        "disabled" => "lightgray",
+        "legacy_run" => "lightblue",
+        _ => "white",
+    }
+}
+
+fn format_dot(output: &Output) -> String {
+    let mut lines = vec![
+        "digraph smf {".to_string(),
+        "    rankdir=LR;".to_string(),
+        "    node [shape=box, style=filled];".to_string(),
+    ];
+
+    for (i, (fmri, state)) in output.nodes.iter().enumerate() {
+        lines.push(format!(
+            "    n{} [label=\"{}\\n({})\", fillcolor={}];",
+            i,
+            fmri,
+            state,
+            dot_color(state)
+        ));
+    }
+
+    for edge in &output.edges {
+        let style = match edge.group.grouping {
+            Grouping::RequireAll => "solid",
+            Grouping::RequireAny => "dashed",
+            Grouping::OptionalAll => "dotted",
+            Grouping::ExcludeAll => "bold, color=red, arrowhead=tee",
+        };
+        lines.push(format!(
+            "    n{} -> n{} [label=\"{}\", style={}];",
+            edge.from,
+            edge.to,
+            edge.group.label(),
+            style
+        ));
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn format_mermaid(output: &Output) -> String {
+    let mut lines = vec![
+        "flowchart LR".to_string(),
+        "    classDef online fill:#98fb98".to_string(),
+        "    classDef offline fill:#f0e68c".to_string(),
+        "    classDef degraded fill:#ffa500".to_string(),
+        "    classDef maintenance fill:#fa8072".to_string(),
+        "    classDef disabled fill:#d3d3d3".to_string(),
+        "    classDef legacy_run fill:#add8e6".to_string(),
+        "    classDef unknown fill:#ffffff,stroke:#ff0000".to_string(),
+    ];
+
+    for (i, (fmri, state)) in output.nodes.iter().enumerate() {
+        let class = match state.as_str() {
+            "uninitialized" => "offline",
+            s @ ("online" | "offline" | "degraded" | "maintenance"
+            | "disabled" | "legacy_run") => s,
+            _ => "unknown",
+        };
+        lines.push(format!(
+            "    n{}[\"{}<br/>({})\"]:::{}",
+            i, fmri, state, class
+        ));
+    }
+
+    for edge in &output.edges {
+        let arrow = match edge.group.grouping {
+            Grouping::RequireAll => "-->",
+            Grouping::RequireAny | Grouping::OptionalAll => "-.->",
+            Grouping::ExcludeAll => "--x",
+        };
+        lines.push(format!(
+            "    n{} {}|{}| n{}",
+            edge.from,
+            arrow,
+            edge.group.label(),
+            edge.to
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn format_json(output: &Output) -> Result<String> {
+    let nodes: Vec<_> = output
+        .nodes
+        .iter()
+        .map(|(fmri, state)| json!({ "fmri": fmri, "state": state }))
+        .collect();
+
+    let fmri = |i: usize| output.nodes.get_index(i).map(|(fmri, _)| fmri);
+    let edges: Vec<_> = output
+        .edges
+        .iter()
+        .map(|edge| {
+            json!({
+                "from": fmri(edge.from),
+                "to": fmri(edge.to),
+                "grouping": edge.group.grouping.to_string(),
+                "restart_on": edge.group.restart_on.to_string(),
+            })
+        })
+        .collect();
+
+    let s = serde_json::to_string_pretty(&json!({
+        "nodes": nodes,
+        "edges": edges,
+    }))?;
+
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVCPROP_OUTPUT: &str = "\
+svc:/a:default/:properties/restarter/state astring online
+svc:/a:default/:properties/b/grouping astring require_all
+svc:/a:default/:properties/b/restart_on astring none
+svc:/a:default/:properties/b/entities fmri svc:/b
+svc:/a:default/:properties/gone/grouping astring exclude_all
+svc:/a:default/:properties/gone/restart_on astring none
+svc:/a:default/:properties/gone/entities fmri svc:/gone
+svc:/b:default/:properties/restarter/state astring offline
+svc:/b:default/:properties/c/grouping astring require_any
+svc:/b:default/:properties/c/restart_on astring restart
+svc:/b:default/:properties/c/entities fmri svc:/c:default
+svc:/c:default/:properties/restarter/state astring online
+svc:/d:default/:properties/restarter/state astring disabled
+svc:/d:default/:properties/b/grouping astring optional_all
+svc:/d:default/:properties/b/restart_on astring none
+svc:/d:default/:properties/b/entities fmri svc:/b:default
+";
+
+    fn output(graph: &Graph) -> Output<'_> {
+        let mut output = Output::default();
+        for svc in graph.services() {
+            output.add_service(graph, &svc.fmri, None);
+        }
+        output
+    }
+
+    #[test]
+    fn test_format_dot() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        let s = format_dot(&output(&graph));
+        let lines: Vec<_> = s.lines().collect();
+
+        assert_eq!(lines[0], "digraph smf {");
+        assert_eq!(
+            lines[3],
+            r#"    n0 [label="svc:/a:default\n(online)", fillcolor=palegreen];"#
+        );
+        assert_eq!(
+            lines[5],
+            r#"    n2 [label="svc:/gone\n(absent)", fillcolor=white];"#
+        );
+        assert_eq!(
+            &lines[8..],
+            [
+                r#"    n0 -> n1 [label="require_all/none", style=solid];"#,
+                r#"    n0 -> n2 [label="exclude_all/none", style=bold, color=red, arrowhead=tee];"#,
+                r#"    n1 -> n3 [label="require_any/restart", style=dashed];"#,
+                r#"    n4 -> n1 [label="optional_all/none", style=dotted];"#,
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_mermaid() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        let s = format_mermaid(&output(&graph));
+        let lines: Vec<_> = s.lines().collect();
+
+        assert_eq!(lines[0], "flowchart LR");
+        assert_eq!(
+            &lines[8..],
+            [
+                r#"    n0["svc:/a:default<br/>(online)"]:::online"#,
+                r#"    n1["svc:/b:default<br/>(offline)"]:::offline"#,
+                r#"    n2["svc:/gone<br/>(absent)"]:::unknown"#,
+                r#"    n3["svc:/c:default<br/>(online)"]:::online"#,
+                r#"    n4["svc:/d:default<br/>(disabled)"]:::disabled"#,
+                "    n0 -->|require_all/none| n1",
+                "    n0 --x|exclude_all/none| n2",
+                "    n1 -.->|require_any/restart| n3",
+                "    n4 -.->|optional_all/none| n1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_json() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        let s = format_json(&output(&graph)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&s).unwrap();
+
+        assert_eq!(value["nodes"].as_array().unwrap().len(), 5);
+        assert_eq!(
+            value["nodes"][2],
+            json!({ "fmri": "svc:/gone", "state": "absent" })
+        );
+        assert_eq!(value["edges"].as_array().unwrap().len(), 4);
+        assert_eq!(
+            value["edges"][2],
+            json!({
+                "from": "svc:/b:default",
+                "to": "svc:/c:default",
+                "grouping": "require_any",
+                "restart_on": "restart",
+            })
+        );
+    }
+
+    #[test]
+    fn test_walk() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+
+        let fmris = walk(&graph, vec!["svc:/a:default".into()], false);
+        assert_eq!(
+            fmris,
+            ["svc:/a:default", "svc:/b:default", "svc:/c:default"]
+        );
+
+        // dependents only link to each other (not to `svc:/gone`)
+        let fmris = walk(&graph, vec!["svc:/c:default".into()], true);
+        assert_eq!(
+            fmris,
+            [
+                "svc:/c:default",
+                "svc:/b:default",
+                "svc:/a:default",
+                "svc:/d:default"
+            ]
+        );
+
+        let mut output = Output::default();
+        for fmri in &fmris {
+            output.add_service(&graph, fmri, Some(&fmris));
+        }
+        let nodes: Vec<_> = output.nodes.keys().collect();
+        assert_eq!(
+            nodes,
+            [
+                "svc:/c:default",
+                "svc:/b:default",
+                "svc:/a:default",
+                "svc:/d:default"
+            ]
+        );
+        let edges: Vec<_> =
+            output.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(edges, [(1, 0), (2, 1), (3, 1)]);
+    }
+}
@@ -0,0 +1,291 @@
+//! `smf impact ...`
+
+use anyhow::{Context, Result};
+use colored::*;
+use indexmap::map::IndexMap;
+use smf::Query;
+
+use crate::util;
+use util::deps::{is_running, DepGroup, Graph, Grouping, RestartOn, Service};
+use util::smf::{
+    get_status_matching, stylize_smf_fmri, stylize_smf_state_small,
+};
+
+use crate::arguments::SubCommandImpact;
+
+/// What happens to a dependent (ordered from least to most severe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Effect {
+    Restart,
+    Offline,
+}
+
+impl Effect {
+    fn stylize(&self) -> String {
+        match self {
+            Effect::Restart => "restart".yellow(),
+            Effect::Offline => "offline".red().bold(),
+        }
+        .to_string()
+    }
+}
+
+struct ImpactPrinter<'a> {
+    graph: &'a Graph,
+    /// The worst effect found for every dependent so far.
+    effects: IndexMap<String, Effect>,
+}
+
+impl ImpactPrinter<'_> {
+    /**
+     * Figure out what happens to a dependent when a service it depends on
+     * (through `group`) goes offline or restarts.
+     *
+     * Whether the dependent is stopped at all depends on its `restart_on`:
+     * only `restart` and `refresh` dependents are stopped when a dependency
+     * is stopped (`none` and `error` ones keep running).  `exclude_all`
+     * dependents are never stopped.
+     *
+     * Whether a stopped dependent comes back depends on the grouping once
+     * the service is offline:
+     *
+     * - `require_all` dependents stay offline
+     * - `require_any` dependents stay offline unless something else in the
+     *   group is still online
+     * - `optional_all` dependents are still satisfied and restart
+     *
+     * Dependents of a service that restarts always come back.
+     */
+    fn effect(
+        &self,
+        fmri: &str,
+        cause: Effect,
+        group: &DepGroup,
+    ) -> Option<Effect> {
+        let stopped = match group.restart_on {
+            RestartOn::Restart | RestartOn::Refresh => true,
+            RestartOn::None | RestartOn::Error => false,
+        };
+        if !stopped || group.grouping == Grouping::ExcludeAll {
+            return None;
+        }
+
+        // another instance that is still running keeps a dependency met
+        let up = |dep: &&Service| {
+            dep.fmri != fmri
+                && is_running(&dep.state)
+                && self.effects.get(&dep.fmri) != Some(&Effect::Offline)
+        };
+        let resolved =
+            group.targets.iter().map(|target| self.graph.resolve(&target.fmri));
+
+        let satisfied = match (cause, group.grouping) {
+            (Effect::Restart, _) => true,
+            // only the targets `fmri` is an instance of are affected
+            (Effect::Offline, Grouping::RequireAll) => resolved
+                .filter(|deps| deps.iter().any(|dep| dep.fmri == fmri))
+                .all(|deps| deps.iter().any(up)),
+            (Effect::Offline, Grouping::RequireAny) => {
+                resolved.flatten().any(|dep| up(&dep))
+            }
+            (Effect::Offline, _) => true,
+        };
+
+        match satisfied {
+            true => Some(Effect::Restart),
+            false => Some(Effect::Offline),
+        }
+    }
+
+    /// Print the dependents affected by `fmri` (recursively) below it.
+    fn print_dependents(&mut self, fmri: &str, cause: Effect, indent: &str) {
+        let mut affected = vec![];
+        for (svc, group) in self.graph.dependents(fmri) {
+            // services that aren't running can't be taken down
+            if !is_running(&svc.state) {
+                continue;
+            }
+            if let Some(effect) = self.effect(fmri, cause, group) {
+                affected.push((svc, group, effect));
+            }
+        }
+
+        let len = affected.len();
+        for (i, (svc, group, effect)) in affected.into_iter().enumerate() {
+            let (branch, next_indent) = match i + 1 == len {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+
+            // only go deeper the first time (or if it got worse)
+            let prev = self.effects.get(&svc.fmri).copied();
+            let expand = prev.is_none_or(|prev| effect > prev);
+
+            let mut line = format!(
+                "{}{}{} {} {} {}",
+                indent,
+                branch.black().bold(),
+                effect.stylize(),
+                stylize_smf_state_small(&svc.state),
+                fmri_name(&svc.fmri),
+                group.label().black().bold()
+            );
+            if !expand {
+                line = format!("{} {}", line, "(see above)".black().bold());
+            }
+            println!("{}", line);
+
+            if expand {
+                self.effects.insert(svc.fmri.clone(), effect);
+                let indent =
+                    format!("{}{}", indent, next_indent.black().bold());
+                self.print_dependents(&svc.fmri, effect, &indent);
+            }
+        }
+    }
+}
+
+pub fn run(cmd: SubCommandImpact) -> Result<()> {
+    let q = Query::new();
+    let (svcs, _) = get_status_matching(&q, &cmd.services)?;
+
+    let graph = Graph::load().context("failed to load dependency graph")?;
+
+    let cause = match cmd.restart {
+        true => Effect::Restart,
+        false => Effect::Offline,
+    };
+
+    for (i, svc) in svcs.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        println!(
+            "{} {} {}",
+            stylize_smf_state_small(&svc.state),
+            fmri_name(&svc.fmri),
+            format!("({})", cause.stylize()).black().bold()
+        );
+
+        let mut printer =
+            ImpactPrinter { graph: &graph, effects: IndexMap::new() };
+        printer.print_dependents(&svc.fmri, cause, "");
+
+        let count =
+            |effect| printer.effects.values().filter(|e| **e == effect).count();
+        println!();
+        println!(
+            "{} service(s) would go offline, {} would restart",
+            count(Effect::Offline).to_string().magenta(),
+            count(Effect::Restart).to_string().magenta()
+        );
+    }
+
+    Ok(())
+}
+
+fn fmri_name(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVCPROP_OUTPUT: &str = "\
+svc:/dep:default/:properties/restarter/state astring online
+svc:/up:default/:properties/restarter/state astring online
+svc:/dn:default/:properties/restarter/state astring offline
+svc:/app:default/:properties/restarter/state astring online
+";
+
+    #[test]
+    fn test_effect() {
+        use Effect::{Offline, Restart};
+
+        // the dependency group of `svc:/app:default` on `svc:/dep` (and
+        // `svc:/up` or `svc:/dn` which are online and offline) with its
+        // effect when `svc:/dep` goes offline and when it restarts
+        let tests = [
+            ("require_all", "none", "", None, None),
+            ("require_all", "error", "", None, None),
+            ("require_all", "restart", "", Some(Offline), Some(Restart)),
+            ("require_all", "refresh", "", Some(Offline), Some(Restart)),
+            ("require_any", "none", "svc:/dn", None, None),
+            ("require_any", "error", "svc:/dn", None, None),
+            ("require_any", "restart", "svc:/dn", Some(Offline), Some(Restart)),
+            ("require_any", "refresh", "svc:/dn", Some(Offline), Some(Restart)),
+            ("require_any", "none", "svc:/up", None, None),
+            ("require_any", "error", "svc:/up", None, None),
+            ("require_any", "restart", "svc:/up", Some(Restart), Some(Restart)),
+            ("require_any", "refresh", "svc:/up", Some(Restart), Some(Restart)),
+            ("optional_all", "none", "", None, None),
+            ("optional_all", "error", "", None, None),
+            ("optional_all", "restart", "", Some(Restart), Some(Restart)),
+            ("optional_all", "refresh", "", Some(Restart), Some(Restart)),
+            ("exclude_all", "none", "", None, None),
+            ("exclude_all", "error", "", None, None),
+            ("exclude_all", "restart", "", None, None),
+            ("exclude_all", "refresh", "", None, None),
+        ];
+
+        for (grouping, restart_on, other, offline, restart) in tests {
+            let pg = "svc:/app:default/:properties/dep";
+            let s = format!(
+                "{}{pg}/grouping astring {}\n\
+                 {pg}/restart_on astring {}\n\
+                 {pg}/entities fmri svc:/dep {}\n",
+                SVCPROP_OUTPUT,
+                grouping,
+                restart_on,
+                other,
+                pg = pg
+            );
+            let graph = Graph::parse(&s).unwrap();
+            let printer =
+                ImpactPrinter { graph: &graph, effects: IndexMap::new() };
+
+            let group = &graph.get("svc:/app:default").unwrap().deps[0];
+            let effect =
+                |cause| printer.effect("svc:/dep:default", cause, group);
+            let name = format!("{}/{} {}", grouping, restart_on, other);
+            assert_eq!(effect(Offline), offline, "{}", name);
+            assert_eq!(effect(Restart), restart, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_effect_instances() {
+        use Effect::{Offline, Restart};
+
+        // `svc:/multi:a` goes offline while `svc:/multi:b` is (or isn't)
+        // still running
+        let tests = [
+            ("online", "svc:/multi", Restart),
+            ("offline", "svc:/multi", Offline),
+            ("online", "svc:/multi:a", Offline),
+            ("online", "svc:/multi svc:/dep", Restart),
+            ("online", "svc:/multi svc:/multi:a", Offline),
+        ];
+
+        for (state, entities, want) in tests {
+            let s = format!(
+                "svc:/multi:a/:properties/restarter/state astring online\n\
+                 svc:/multi:b/:properties/restarter/state astring {}\n\
+                 {}\
+                 svc:/app:default/:properties/dep/grouping astring require_all\n\
+                 svc:/app:default/:properties/dep/restart_on astring restart\n\
+                 svc:/app:default/:properties/dep/entities fmri {}\n",
+                state, SVCPROP_OUTPUT, entities
+            );
+            let graph = Graph::parse(&s).unwrap();
+            let printer =
+                ImpactPrinter { graph: &graph, effects: IndexMap::new() };
+
+            let group = &graph.get("svc:/app:default").unwrap().deps[0];
+            let effect = printer.effect("svc:/multi:a", Offline, group);
+            assert_eq!(effect, Some(want), "{} {}", state, entities);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! `smf kill ...`
+
+use std::io::{self, Write};
+
+use anyhow::{bail, Result};
+use colored::*;
+use smf::Query;
+
+use crate::util::smf::{
+    get_contract_members, get_status_matching, stylize_smf_fmri,
+    stylize_smf_state_small,
+};
+
+use crate::arguments::SubCommandKill;
+
+const SIGNALS: [(&str, libc::c_int); 12] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ABRT", libc::SIGABRT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("STOP", libc::SIGSTOP),
+    ("CONT", libc::SIGCONT),
+    ("WINCH", libc::SIGWINCH),
+];
+
+pub fn run(cmd: SubCommandKill) -> Result<()> {
+    let (sig_name, sig) = parse_signal(&cmd.signal)?;
+
+    let (svcs, _) = get_status_matching(&Query::new(), &cmd.services)?;
+
+    if svcs.is_empty() {
+        bail!("no services found for: {:?}", cmd.services);
+    }
+
+    // gather the processes to signal for each service
+    let mut targets = vec![];
+    for svc in svcs {
+        let pids = match svc.contract_id {
+            Some(ctid) => get_contract_members(ctid).unwrap_or_default(),
+            None => vec![],
+        };
+
+        let state = stylize_smf_state_small(&svc.state);
+        let fmri = stylize_smf_fmri(&svc.fmri)?;
+        let s = match pids.is_empty() {
+            true => "no processes".yellow().to_string(),
+            false => {
+                let pids: Vec<_> = pids
+                    .iter()
+                    .map(|p| p.to_string().cyan().to_string())
+                    .collect();
+                pids.join(", ")
+            }
+        };
+        eprintln!("{} {}: {}", state, fmri, s);
+
+        targets.push((svc.fmri, pids));
+    }
+
+    let num_pids: usize = targets.iter().map(|(_, pids)| pids.len()).sum();
+    if num_pids == 0 {
+        bail!("no processes found for: {:?}", cmd.services);
+    }
+
+    if cmd.dry_run {
+        return Ok(());
+    }
+
+    // confirm with the user when the pattern(s) matched more than we expected
+    if targets.len() > 1 && !cmd.yes {
+        let prompt = format!(
+            "send SIG{} to {} processes in {} services? [y/N] ",
+            sig_name,
+            num_pids,
+            targets.len()
+        );
+        if !confirm(&prompt)? {
+            bail!("aborted");
+        }
+    }
+
+    // send the signal
+    let mut failed = vec![];
+    for (fmri, pids) in targets {
+        for pid in pids {
+            let ret = unsafe { libc::kill(pid as libc::pid_t, sig) };
+            if ret != 0 {
+                let err = io::Error::last_os_error();
+                eprintln!("failed to signal {} ({}): {}", pid, fmri, err);
+                failed.push(pid);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("failed to send SIG{} to pid(s): {:?}", sig_name, failed);
+    }
+
+    Ok(())
+}
+
+/// Parse a signal given as a name (`TERM`, `SIGTERM`) or number (`15`).
+fn parse_signal(s: &str) -> Result<(String, libc::c_int)> {
+    if let Ok(num) = s.parse::<libc::c_int>() {
+        let name = SIGNALS
+            .iter()
+            .find(|(_, sig)| *sig == num)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| num.to_string());
+        return Ok((name, num));
+    }
+
+    let upper = s.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+
+    match SIGNALS.iter().find(|(n, _)| *n == name) {
+        Some((name, sig)) => Ok((name.to_string(), *sig)),
+        None => bail!("unknown signal: {}", s),
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
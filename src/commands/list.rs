@@ -1,15 +1,17 @@
 //! `smf list ...`
 
+use std::path::Path;
+
 use anyhow::{Context, Result};
 use chrono::Utc;
 use colored::*;
-use libcontract::status::{ContractStatus, Detail};
 use smf::{Query, SmfState};
 
 use crate::util;
 use util::color_aware_string::ColorAwareString;
+use util::proc::{get_usernames, ProcReader, ProcTree};
 use util::smf::{
-    get_ptree_for_fmri, parse_smf_date, stylize_smf_date, stylize_smf_fmri,
+    get_contract_members, parse_smf_date, stylize_smf_date, stylize_smf_fmri,
     stylize_smf_state_small,
 };
 
@@ -23,6 +25,13 @@ pub fn run(cmd: SubCommandList) -> Result<()> {
 
     let now = Utc::now().naive_utc();
 
+    // only needed when printing process trees
+    let reader = ProcReader::new();
+    let users = match cmd.tree {
+        true => get_usernames(Path::new("/etc/passwd")).unwrap_or_default(),
+        false => Default::default(),
+    };
+
     // sort services by fields given
     for sort in cmd.sort {
         match sort {
@@ -79,9 +88,12 @@ pub fn run(cmd: SubCommandList) -> Result<()> {
 
         println!("{}", format_output_line(&[state, fmri, ctid, pids, time]));
 
-        if cmd.tree && svc.contract_id.is_some() {
-            let ptree = get_ptree_for_fmri(&svc.fmri)?;
-            println!("\n{}\n", ptree.bold().black());
+        if let (true, Some(ctid)) = (cmd.tree, svc.contract_id) {
+            let members = get_contract_members(ctid).unwrap_or_default();
+            let tree = ProcTree::from_pids(&reader, &members);
+            if !tree.is_empty() {
+                println!("\n{}\n", tree.render(&users));
+            }
         }
     }
 
@@ -101,9 +113,7 @@ fn stylize_contract_id(ctid: &Option<usize>) -> String {
 fn stylize_pids(ctid: &Option<usize>) -> String {
     match ctid {
         Some(ctid) => {
-            let ctid = *ctid as u32;
-            let status = ContractStatus::new(ctid, Detail::All).unwrap();
-            let members = status.get_members().unwrap();
+            let members = get_contract_members(*ctid).unwrap();
             match members.len() {
                 0 => "0".yellow(),
                 n => n.to_string().green(),
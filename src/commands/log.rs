@@ -1,47 +1,749 @@
 //! `smf log ...`
 
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
 use anyhow::{bail, Context, Result};
+use chrono::{NaiveDateTime, Utc};
 use colored::*;
-use exec::Command;
+use regex::Regex;
 use smf::Query;
 
-use crate::arguments::SubCommandLog;
+use crate::util;
+use util::bunyan::{self, Level};
+use util::color_aware_string::ColorAwareString;
+use util::restarter;
+use util::rotated::{find_rotated, read_log_lines};
+use util::smf::{fmri_short_name, get_status_matching, stylize_smf_fmri};
+use util::tail::{
+    bisect_lines, find_last_line_matching, find_last_lines_offset,
+    read_lines_from, Follower,
+};
+use util::timestamp::{
+    merge_by_timestamp, parse_line_timestamp, parse_time_arg,
+};
+
+use crate::arguments::{LogFormat, LogLevel, SubCommandLog};
+
+/// Log files at least this big are highlighted when listing.
+const LARGE_LOG_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Default number of lines to show (same as `tail`).
+const DEFAULT_LINES: u32 = 10;
+
+/// How often to check log files for new data when following.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Colors used (in order) to prefix lines from each service when merging.
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Where to start reading each log file from.
+enum Start {
+    /// The last `n` lines.
+    Lines(usize),
+    /// The last time the service was started.
+    LastRestart,
+    /// The first line logged at or after the given time.
+    Since(NaiveDateTime),
+}
+
+/// A log file and the service it belongs to.
+struct LogFile {
+    path: PathBuf,
+    fmri: String,
+    /// Colorized (and padded) service name used when merging.
+    prefix: String,
+}
+
+/// Formats (and filters) log lines based on the options given.
+struct LineFormatter {
+    now: NaiveDateTime,
+    format: LogFormat,
+    level: Option<Level>,
+    events_only: bool,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+}
+
+impl LineFormatter {
+    /// Format a line for output, or `None` if it should be skipped.
+    fn format(&self, line: &str) -> Option<String> {
+        let raw = matches!(self.format, LogFormat::Raw);
+
+        // lines written by the restarter itself
+        if let Some(event) = restarter::parse_event(line) {
+            return match raw {
+                true => Some(line.to_string()),
+                false => Some(event.stylize(line)),
+            };
+        }
+        if self.events_only {
+            return None;
+        }
+
+        if raw && self.level.is_none() {
+            return Some(line.to_string());
+        }
+
+        // non-JSON lines are always passed through
+        let obj = match bunyan::parse_json(line) {
+            Some(obj) => obj,
+            None => return Some(line.to_string()),
+        };
+
+        if let Some(min) = self.level {
+            if bunyan::record_level(&obj).is_some_and(|level| level < min) {
+                return None;
+            }
+        }
+
+        let s = match self.format {
+            LogFormat::Raw => line.to_string(),
+            LogFormat::Json => bunyan::format_json(&obj),
+            LogFormat::Bunyan => bunyan::format_record(&obj),
+            LogFormat::Auto if bunyan::is_bunyan_record(&obj) => {
+                bunyan::format_record(&obj)
+            }
+            LogFormat::Auto => line.to_string(),
+        };
+
+        Some(s)
+    }
+
+    /**
+     * Drop lines outside of the `--since` / `--until` range (if given).
+     *
+     * Lines without a timestamp inherit the timestamp of the line before
+     * them.  `default` is used for lines that come before any timestamp.
+     */
+    fn filter_time_range(
+        &self,
+        lines: Vec<String>,
+        default: Option<NaiveDateTime>,
+    ) -> Vec<String> {
+        if self.since.is_none() && self.until.is_none() {
+            return lines;
+        }
+
+        let mut last = default;
+        lines
+            .into_iter()
+            .filter(|line| {
+                last = parse_line_timestamp(&self.now, line).or(last);
+                last.is_some_and(|ts| {
+                    self.since.is_none_or(|since| ts >= since)
+                        && self.until.is_none_or(|until| ts <= until)
+                })
+            })
+            .collect()
+    }
+
+    /// Format and print a line (with an optional prefix on every line).
+    fn print(&self, prefix: Option<&str>, line: &str) {
+        let s = match self.format(line) {
+            Some(s) => s,
+            None => return,
+        };
+
+        match prefix {
+            Some(prefix) => {
+                for line in s.lines() {
+                    println!("{} {}", prefix, line);
+                }
+            }
+            None => println!("{}", s),
+        }
+    }
+}
 
 pub fn run(cmd: SubCommandLog) -> Result<()> {
     // get log files for the service arguments
-    let log_files: Vec<String> = Query::new()
-        .get_log_files(&cmd.services)
-        .with_context(|| {
-            format!(
-                "failed to get log files for service(s): {:?}",
-                cmd.services
-            )
-        })?
-        .map(|p| p.into_os_string().into_string().unwrap())
-        .collect();
+    let log_files = get_log_files(&cmd.services).with_context(|| {
+        format!("failed to get log files for service(s): {:?}", cmd.services)
+    })?;
 
     if log_files.is_empty() {
         bail!("no log files found for: {:?}", cmd.services);
     }
 
-    // construct arguments for `tail`
-    let mut args: Vec<String> = vec!["tail".into()];
-    if cmd.follow {
-        args.push("-F".into());
+    if cmd.list {
+        return list_files(&log_files);
+    }
+
+    for log_file in &log_files {
+        eprintln!("- {}", log_file.path.display().to_string().cyan());
+    }
+
+    let now = Utc::now().naive_utc();
+    let since = cmd.since.map(|s| parse_time_arg(&now, &s)).transpose()?;
+    let until = cmd.until.map(|s| parse_time_arg(&now, &s)).transpose()?;
+
+    let start = if cmd.since_restart {
+        Start::LastRestart
+    } else if since.is_some() || until.is_some() {
+        Start::Since(since.unwrap_or(NaiveDateTime::MIN))
+    } else {
+        Start::Lines(cmd.number.unwrap_or(DEFAULT_LINES) as usize)
+    };
+    let fmt = LineFormatter {
+        now,
+        format: cmd.format,
+        level: cmd.level.map(|level| match level {
+            LogLevel::Trace => Level::Trace,
+            LogLevel::Debug => Level::Debug,
+            LogLevel::Info => Level::Info,
+            LogLevel::Warn => Level::Warn,
+            LogLevel::Error => Level::Error,
+            LogLevel::Fatal => Level::Fatal,
+        }),
+        events_only: cmd.events_only,
+        since,
+        until,
+    };
+
+    if let Some(pattern) = &cmd.grep {
+        return grep_files(&log_files, pattern, cmd.context, cmd.rotated);
+    }
+
+    let rotated = cmd.rotated;
+    match (cmd.follow, cmd.merge) {
+        (true, merge) => follow_files(&fmt, &log_files, &start, merge, rotated),
+        (false, true) => print_merged(&fmt, &log_files, &start, rotated),
+        (false, false) => print_files(&fmt, &log_files, &start, rotated),
     }
-    if let Some(number) = cmd.number {
-        args.push("-n".into());
-        args.push(number.to_string());
+}
+
+/// Get the log files for all services matching the patterns given.
+fn get_log_files(services: &[String]) -> Result<Vec<LogFile>> {
+    let q = Query::new();
+    let (svcs, _) = get_status_matching(&q, services)?;
+
+    let mut found = vec![];
+    for (i, svc) in svcs.into_iter().enumerate() {
+        let color = PREFIX_COLORS[i % PREFIX_COLORS.len()];
+        let name = fmri_short_name(&svc.fmri);
+        let cur_svc = &[&svc.fmri];
+
+        let paths = q.get_log_files(cur_svc).with_context(|| {
+            format!("failed to get_log_files: {}", &svc.fmri)
+        })?;
+        for path in paths {
+            found.push((path, svc.fmri.clone(), name.clone(), color));
+        }
     }
-    args.extend_from_slice(&log_files);
 
+    // pad every prefix to the same width so the lines align
+    let width =
+        found.iter().map(|(_, _, name, _)| name.len()).max().unwrap_or(0);
+    let log_files = found
+        .into_iter()
+        .map(|(path, fmri, name, color)| {
+            let name = ColorAwareString::with_string(name).pad_end(width);
+            let prefix = format!("{} |", name).color(color).to_string();
+            LogFile { path, fmri, prefix }
+        })
+        .collect();
+
+    Ok(log_files)
+}
+
+/// Print the log files of every service with their size, modified time and
+/// rotated files.
+fn list_files(log_files: &[LogFile]) -> Result<()> {
+    let now = SystemTime::now();
+    let mut failed = vec![];
+
+    println!();
+    println!(
+        "{}",
+        format_output_line(&[
+            "SIZE".bold().to_string(),
+            "MODIFIED".bold().to_string(),
+            "ROTATED".bold().to_string(),
+            "PATH".bold().to_string(),
+        ])
+    );
+
+    let mut last_fmri = None;
     for log_file in log_files {
-        eprintln!("- {}", log_file.cyan());
+        if last_fmri != Some(&log_file.fmri) {
+            println!("{}", stylize_smf_fmri(&log_file.fmri)?);
+            last_fmri = Some(&log_file.fmri);
+        }
+
+        let path = log_file.path.display().to_string();
+        let (size, modified) = match fs::metadata(&log_file.path) {
+            Ok(md) => {
+                let age = md.modified().map(|t| now.duration_since(t));
+                let modified = match age {
+                    Ok(Ok(age)) => util::relative_duration(&age),
+                    _ => "-".to_string(),
+                };
+                (stylize_size(md.len()), modified)
+            }
+            Err(err) => {
+                eprintln!("failed to stat {}: {}", path, err);
+                failed.push(&log_file.path);
+                ("-".to_string(), "-".to_string())
+            }
+        };
+
+        let rotated = find_rotated(&log_file.path).unwrap_or_else(|err| {
+            eprintln!("{:#}", err);
+            vec![]
+        });
+        let rotated = match rotated.len() {
+            0 => "-".to_string(),
+            n => {
+                let total: u64 = rotated
+                    .iter()
+                    .filter_map(|path| fs::metadata(path).ok())
+                    .map(|md| md.len())
+                    .sum();
+                format!("{} ({})", n, util::human_size(total))
+                    .magenta()
+                    .to_string()
+            }
+        };
+
+        println!(
+            "{}",
+            format_output_line(&[
+                size,
+                modified,
+                rotated,
+                path.cyan().to_string()
+            ])
+        );
+    }
+
+    println!();
+
+    if !failed.is_empty() {
+        bail!("failed to stat log file(s): {:?}", failed);
     }
 
-    // fork `tail`
-    let err = Command::new(&args[0]).args(&args[1..]).exec();
+    Ok(())
+}
+
+/// Colorize a file size based on how big it is.
+fn stylize_size(size: u64) -> String {
+    let s = util::human_size(size);
+
+    match size {
+        n if n >= LARGE_LOG_SIZE => s.red().bold().to_string(),
+        n if n >= LARGE_LOG_SIZE / 10 => s.yellow().to_string(),
+        _ => s,
+    }
+}
+
+fn format_output_line<T: AsRef<str>>(cols: &[T]) -> String {
+    let data = [
+        (cols[0].as_ref(), 8),
+        (cols[1].as_ref(), 12),
+        (cols[2].as_ref(), 12),
+        (cols[3].as_ref(), 0),
+    ];
+
+    let mut line = String::new();
+
+    for (text, max) in data {
+        let cas = ColorAwareString::with_string(text.into());
+
+        line.push_str("    ");
+        let padded = cas.pad_end(max);
+        line.push_str(&padded);
+    }
+
+    line
+}
+
+/// Print the last lines of each file (like `tail -n`).
+fn print_files(
+    fmt: &LineFormatter,
+    log_files: &[LogFile],
+    start: &Start,
+    rotated: bool,
+) -> Result<()> {
+    let multiple = log_files.len() > 1;
+    let mut failed = vec![];
+
+    for (i, log_file) in log_files.iter().enumerate() {
+        let res = read_start_lines(fmt, &log_file.path, start, rotated);
+        let lines = match res {
+            Ok(lines) => lines,
+            Err(err) => {
+                eprintln!("{:#}", err);
+                failed.push(&log_file.path);
+                continue;
+            }
+        };
+
+        if multiple {
+            if i > 0 {
+                println!();
+            }
+            print_header(&log_file.path);
+        }
+
+        for line in lines {
+            fmt.print(None, &line);
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("failed to read log file(s): {:?}", failed);
+    }
+
+    Ok(())
+}
+
+/// Print the last lines of each file interleaved by timestamp.
+fn print_merged(
+    fmt: &LineFormatter,
+    log_files: &[LogFile],
+    start: &Start,
+    rotated: bool,
+) -> Result<()> {
+    let mut sources = vec![];
+    let mut failed = vec![];
+    for log_file in log_files {
+        let lines = read_start_lines(fmt, &log_file.path, start, rotated)
+            .unwrap_or_else(|err| {
+                eprintln!("{:#}", err);
+                failed.push(&log_file.path);
+                vec![]
+            });
+        sources.push(lines);
+    }
+
+    print_merged_lines(fmt, log_files, sources);
+
+    if !failed.is_empty() {
+        bail!("failed to read log file(s): {:?}", failed);
+    }
+
+    Ok(())
+}
+
+/// Search every log file (oldest rotated file first) for a pattern.
+fn grep_files(
+    log_files: &[LogFile],
+    pattern: &str,
+    context: usize,
+    rotated: bool,
+) -> Result<()> {
+    let re = Regex::new(pattern)
+        .with_context(|| format!("invalid pattern: {}", pattern))?;
+
+    let mut matches = 0;
+    let mut printed = false;
+    let mut failed = vec![];
+
+    for log_file in log_files {
+        let mut paths = match rotated {
+            true => find_rotated(&log_file.path).unwrap_or_else(|err| {
+                eprintln!("{:#}", err);
+                vec![]
+            }),
+            false => vec![],
+        };
+        paths.push(log_file.path.clone());
+
+        for path in paths {
+            let lines = match read_log_lines(&path) {
+                Ok(lines) => lines,
+                Err(err) => {
+                    eprintln!("{:#}", err);
+                    failed.push(path);
+                    continue;
+                }
+            };
+
+            matches += grep_lines(
+                &re,
+                &log_file.prefix,
+                &path,
+                &lines,
+                context,
+                &mut printed,
+            );
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("failed to read log file(s): {:?}", failed);
+    }
+
+    if matches == 0 {
+        bail!("no lines found matching: {}", pattern);
+    }
+
+    Ok(())
+}
+
+/**
+ * Print the lines matching a regex (and any context around them) like
+ * `grep -n`:
+ *
+ * ```text
+ * app | /var/svc/log/app:default.log-11-context
+ * app | /var/svc/log/app:default.log:12:match
+ * --
+ * ```
+ *
+ * `printed` tracks whether anything has been printed yet (across files) so
+ * groups of context can be separated.  Returns the number of matching lines.
+ */
+fn grep_lines(
+    re: &Regex,
+    prefix: &str,
+    path: &Path,
+    lines: &[String],
+    context: usize,
+    printed: &mut bool,
+) -> usize {
+    let hits: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| re.is_match(line))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut last: Option<usize> = None;
+    for &i in &hits {
+        let mut from = i.saturating_sub(context);
+        let to = (i + context).min(lines.len() - 1);
+
+        // continue the previous group if it overlaps (or touches) this one
+        match last {
+            Some(last) if from <= last + 1 => from = from.max(last + 1),
+            _ => {
+                if *printed && context > 0 {
+                    println!("{}", "--".cyan());
+                }
+            }
+        }
+
+        for (j, line) in lines.iter().enumerate().take(to + 1).skip(from) {
+            let (sep, line) = match re.is_match(line) {
+                true => (":", highlight_matches(re, line)),
+                false => ("-", line.to_string()),
+            };
+            println!(
+                "{} {}{}{}{}{}",
+                prefix,
+                path.display().to_string().magenta(),
+                sep.cyan(),
+                (j + 1).to_string().green(),
+                sep.cyan(),
+                line
+            );
+        }
+
+        last = Some(to);
+        *printed = true;
+    }
+
+    hits.len()
+}
+
+fn highlight_matches(re: &Regex, line: &str) -> String {
+    re.replace_all(line, |caps: &regex::Captures| {
+        caps[0].red().bold().to_string()
+    })
+    .to_string()
+}
+
+/// Print the last lines of each file and then follow them (like `tail -F`).
+fn follow_files(
+    fmt: &LineFormatter,
+    log_files: &[LogFile],
+    start: &Start,
+    merge: bool,
+    rotated: bool,
+) -> Result<()> {
+    let multiple = log_files.len() > 1;
+    let mut last_printed = None;
+
+    // rotated files can't be followed - print everything from them (and the
+    // current files) first and then only follow new lines
+    let start = match rotated {
+        true => {
+            let res = match merge {
+                true => print_merged(fmt, log_files, start, true),
+                false => print_files(fmt, log_files, start, true),
+            };
+            if let Err(err) = res {
+                eprintln!("{:#}", err);
+            }
+            last_printed = log_files.len().checked_sub(1);
+            &Start::Lines(0)
+        }
+        false => start,
+    };
+
+    // start each follower far enough back to print the initial lines
+    let mut followers = vec![];
+    for log_file in log_files {
+        let offset = find_start_offset(fmt, &log_file.path, start)
+            .unwrap_or_else(|err| {
+                eprintln!("{:#}", err);
+                0
+            });
+        followers.push(Follower::new(&log_file.path, offset));
+    }
+
+    loop {
+        if merge {
+            let now = Utc::now().naive_utc();
+            let sources = followers
+                .iter_mut()
+                .map(|f| Ok(fmt.filter_time_range(f.poll()?, Some(now))))
+                .collect::<Result<_>>()?;
+            print_merged_lines(fmt, log_files, sources);
+        } else {
+            for (i, follower) in followers.iter_mut().enumerate() {
+                let now = Utc::now().naive_utc();
+                let lines = fmt.filter_time_range(follower.poll()?, Some(now));
+                if lines.is_empty() {
+                    continue;
+                }
+
+                // print a header whenever we switch files
+                if multiple && last_printed != Some(i) {
+                    if last_printed.is_some() {
+                        println!();
+                    }
+                    print_header(follower.path());
+                    last_printed = Some(i);
+                }
+
+                for line in lines {
+                    fmt.print(None, &line);
+                }
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn print_merged_lines(
+    fmt: &LineFormatter,
+    log_files: &[LogFile],
+    sources: Vec<Vec<String>>,
+) {
+    let now = Utc::now().naive_utc();
+
+    for (i, line) in merge_by_timestamp(&now, sources) {
+        fmt.print(Some(&log_files[i].prefix), &line);
+    }
+}
+
+/// Find the byte offset to start reading the log file from.
+fn find_start_offset(
+    fmt: &LineFormatter,
+    log_file: &Path,
+    start: &Start,
+) -> Result<u64> {
+    let mut file = File::open(log_file)
+        .with_context(|| format!("failed to open {}", log_file.display()))?;
+
+    let offset = match start {
+        Start::Lines(n) => find_last_lines_offset(&mut file, *n)?,
+        Start::LastRestart => {
+            // if the service was started before the log file was created
+            // (ie. rotated) the whole file is from the current instance
+            find_last_line_matching(&mut file, |line| {
+                restarter::parse_event(line)
+                    .is_some_and(|event| event.is_start())
+            })?
+            .unwrap_or(0)
+        }
+        Start::Since(since) => bisect_lines(&mut file, |line| {
+            parse_line_timestamp(&fmt.now, line).map(|ts| ts < *since)
+        })?,
+    };
+
+    Ok(offset)
+}
+
+/**
+ * Read the lines of the log file from where it should start.
+ *
+ * If `rotated` is set the start is found across the rotated files as well,
+ * reading them newest first and only going back as far as needed.
+ */
+fn read_start_lines(
+    fmt: &LineFormatter,
+    log_file: &Path,
+    start: &Start,
+    rotated: bool,
+) -> Result<Vec<String>> {
+    if !rotated {
+        let offset = find_start_offset(fmt, log_file, start)?;
+        let lines = read_lines_from(log_file, offset)?;
+        return Ok(fmt.filter_time_range(lines, None));
+    }
+
+    let mut paths = find_rotated(log_file)?;
+    paths.push(log_file.to_path_buf());
+
+    let mut chunks = vec![];
+    let mut total = 0;
+    for path in paths.iter().rev() {
+        let chunk = read_log_lines(path)?;
+        total += chunk.len();
+
+        let found = match start {
+            Start::Lines(n) => total >= *n,
+            Start::LastRestart => chunk.iter().any(|line| {
+                restarter::parse_event(line)
+                    .is_some_and(|event| event.is_start())
+            }),
+            // older files can only have lines from before this one
+            Start::Since(since) => chunk
+                .iter()
+                .find_map(|line| parse_line_timestamp(&fmt.now, line))
+                .is_some_and(|ts| ts < *since),
+        };
+
+        chunks.push(chunk);
+        if found {
+            break;
+        }
+    }
+
+    chunks.reverse();
+    let mut lines = chunks.concat();
+
+    let skip = match start {
+        Start::Lines(n) => lines.len().saturating_sub(*n),
+        Start::LastRestart => lines
+            .iter()
+            .rposition(|line| {
+                restarter::parse_event(line)
+                    .is_some_and(|event| event.is_start())
+            })
+            .unwrap_or(0),
+        // filtered by time below
+        Start::Since(_) => 0,
+    };
+    lines.drain(..skip);
+
+    Ok(fmt.filter_time_range(lines, None))
+}
 
-    // exec failed if we are here
-    Err(err).with_context(|| format!("failed to exec: {}", args.join(" ")))
+fn print_header(log_file: &Path) {
+    println!("==> {} <==", log_file.display());
 }
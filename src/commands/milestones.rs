@@ -0,0 +1,244 @@
+//! `smf milestones ...`
+
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use colored::*;
+
+use crate::util;
+use util::color_aware_string::ColorAwareString;
+use util::deps::{is_running, state_name, Graph};
+use util::smf::{stylize_smf_fmri, stylize_smf_state_small};
+
+use crate::arguments::SubCommandMilestones;
+
+/// The restarter whose properties hold the milestone the system boots to.
+const RESTARTER_FMRI: &str = "svc:/system/svc/restarter:default";
+
+/// How many blocking services to list per milestone without `--long`.
+const MAX_BLOCKERS: usize = 5;
+
+pub fn run(cmd: SubCommandMilestones) -> Result<()> {
+    let graph = Graph::load().context("failed to load dependency graph")?;
+
+    let current = current_milestone();
+    println!();
+    println!(
+        "current milestone: {}",
+        match &current {
+            Ok(Some(fmri)) => fmri_name(fmri),
+            Ok(None) => "all".green().to_string(),
+            Err(err) => format!("{} ({:#})", "unknown".yellow(), err),
+        }
+    );
+
+    // every milestone with the services it (transitively) depends on
+    let mut milestones: Vec<_> = graph
+        .services()
+        .filter(|svc| svc.fmri.starts_with("svc:/milestone/"))
+        .map(|svc| (svc, graph.members(&svc.fmri)))
+        .collect();
+    milestones.sort_by_key(|(_, members)| members.len());
+
+    println!();
+    println!(
+        "{}",
+        format_output_line(&[
+            "".bold().to_string(),
+            "MILESTONE".bold().to_string(),
+            "MEMBERS".bold().to_string(),
+            "ONLINE".bold().to_string(),
+            "BLOCKING".bold().to_string(),
+        ])
+    );
+
+    let mut blocked = vec![];
+    for (svc, members) in &milestones {
+        let online = members
+            .keys()
+            .filter(|fmri| {
+                graph.get(fmri).is_some_and(|m| is_running(&m.state))
+            })
+            .count();
+        let blockers: Vec<_> = members
+            .iter()
+            .filter(|(_, required)| **required)
+            .filter_map(|(fmri, _)| graph.get(fmri))
+            .filter(|m| !is_running(&m.state))
+            .collect();
+
+        let mut name = fmri_name(&svc.fmri);
+        if current.as_ref().is_ok_and(|c| c.as_ref() == Some(&svc.fmri)) {
+            name = format!("{} {}", name, "(current)".green());
+        }
+        let online = match online == members.len() {
+            true => online.to_string().green(),
+            false => online.to_string().yellow(),
+        };
+        let blocking = match blockers.len() {
+            0 => "0".green(),
+            n => n.to_string().red(),
+        };
+
+        println!(
+            "{}",
+            format_output_line(&[
+                stylize_smf_state_small(&svc.state),
+                name,
+                members.len().to_string().magenta().to_string(),
+                online.to_string(),
+                blocking.to_string(),
+            ])
+        );
+
+        if !blockers.is_empty() {
+            blocked.push((svc, blockers));
+        }
+    }
+
+    if cmd.long {
+        for (svc, members) in &milestones {
+            println!();
+            println!("{} members:", fmri_name(&svc.fmri));
+            for (fmri, required) in members {
+                let m = match graph.get(fmri) {
+                    Some(m) => m,
+                    None => continue,
+                };
+                let mut line = format!(
+                    "  {} {} ({})",
+                    stylize_smf_state_small(&m.state),
+                    fmri_name(&m.fmri),
+                    state_name(&m.state)
+                );
+                if !required {
+                    line = format!("{} {}", line, "optional".black().bold());
+                }
+                println!("{}", line);
+            }
+        }
+    }
+
+    for (svc, blockers) in blocked {
+        if !cmd.long && blockers.len() > MAX_BLOCKERS {
+            println!();
+            println!(
+                "{} blocked by {} services (use -l to list them)",
+                fmri_name(&svc.fmri),
+                blockers.len().to_string().red()
+            );
+            continue;
+        }
+
+        println!();
+        println!("{} blocked by:", fmri_name(&svc.fmri));
+        for m in blockers {
+            println!(
+                "  {} {} ({})",
+                stylize_smf_state_small(&m.state),
+                fmri_name(&m.fmri),
+                state_name(&m.state)
+            );
+        }
+    }
+
+    println!();
+
+    Ok(())
+}
+
+/// Get the milestone the system is booted to (`None` means all services).
+fn current_milestone() -> Result<Option<String>> {
+    // `svcadm milestone` sets options_ovr, `svcadm milestone -d` sets options
+    for prop in ["options_ovr/milestone", "options/milestone"] {
+        let output = Command::new("svcprop")
+            .args(["-p", prop, RESTARTER_FMRI])
+            .output()
+            .context("failed to run svcprop")?;
+        if !output.status.success() {
+            continue;
+        }
+
+        let value = String::from_utf8_lossy(&output.stdout);
+        return Ok(parse_milestone(value.trim()));
+    }
+
+    Ok(None)
+}
+
+/**
+ * Parse a milestone the way `svcadm milestone` accepts it: `all`, `none`,
+ * a milestone name (or run level) or an FMRI.
+ */
+fn parse_milestone(s: &str) -> Option<String> {
+    let name = match s {
+        "" | "all" => return None,
+        "none" => return Some(s.to_string()),
+        "s" | "S" => "single-user",
+        "2" => "multi-user",
+        "3" => "multi-user-server",
+        _ => s,
+    };
+
+    let fmri = match name.starts_with("svc:/") {
+        true => name.to_string(),
+        false => format!("svc:/milestone/{}", name),
+    };
+
+    // the instance is implied
+    match fmri.matches(':').count() {
+        1 => Some(format!("{}:default", fmri)),
+        _ => Some(fmri),
+    }
+}
+
+fn fmri_name(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+fn format_output_line<T: AsRef<str>>(cols: &[T]) -> String {
+    let data = [
+        (cols[0].as_ref(), 1),
+        (cols[1].as_ref(), 45),
+        (cols[2].as_ref(), 8),
+        (cols[3].as_ref(), 7),
+        (cols[4].as_ref(), 8),
+    ];
+
+    let mut line = String::new();
+
+    for (text, max) in data {
+        let cas = ColorAwareString::with_string(text.into());
+
+        line.push(' ');
+        let padded = cas.pad_end(max);
+        line.push_str(&padded);
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_milestone() {
+        let single = Some("svc:/milestone/single-user:default");
+        let tests = [
+            ("", None),
+            ("all", None),
+            ("none", Some("none")),
+            ("S", single),
+            ("single-user", single),
+            ("svc:/milestone/single-user", single),
+            ("svc:/milestone/single-user:default", single),
+            ("2", Some("svc:/milestone/multi-user:default")),
+            ("3", Some("svc:/milestone/multi-user-server:default")),
+        ];
+
+        for (s, want) in tests {
+            assert_eq!(parse_milestone(s).as_deref(), want, "{}", s);
+        }
+    }
+}
@@ -1,5 +1,17 @@
+pub mod blame;
+pub mod check;
+pub mod deps;
 pub mod disable;
 pub mod enable;
+pub mod graph;
+pub mod impact;
+pub mod kill;
 pub mod list;
 pub mod log;
+pub mod milestones;
+pub mod ps;
 pub mod status;
+pub mod top;
+pub mod wait;
+pub mod which;
+pub mod why_offline;
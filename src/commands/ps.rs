@@ -0,0 +1,178 @@
+//! `smf ps ...`
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use colored::*;
+use smf::Query;
+
+use crate::util;
+use util::color_aware_string::ColorAwareString;
+use util::proc::{get_usernames, username, ProcReader};
+use util::smf::{
+    get_contract_members, get_status_matching, stylize_smf_fmri,
+    stylize_smf_state_small,
+};
+
+use crate::arguments::SubCommandPs;
+
+pub fn run(cmd: SubCommandPs) -> Result<()> {
+    let (svcs, _) = get_status_matching(&Query::new(), &cmd.services)?;
+
+    let reader = ProcReader::new();
+    let users = get_usernames(Path::new("/etc/passwd")).unwrap_or_default();
+    let now = Local::now().timestamp();
+
+    for svc in svcs {
+        // only services with a contract have processes
+        let ctid = match svc.contract_id {
+            Some(ctid) => ctid,
+            None => continue,
+        };
+
+        let state = stylize_smf_state_small(&svc.state);
+        let fmri = stylize_smf_fmri(&svc.fmri)?;
+
+        println!();
+        println!("{} {} ({})", state, fmri, ctid.to_string().magenta());
+
+        let mut procs: Vec<_> = get_contract_members(ctid)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|pid| reader.read(pid).ok())
+            .collect();
+        procs.sort_by_key(|p| p.pid);
+
+        if procs.is_empty() {
+            println!("  {}", "no processes".yellow());
+            continue;
+        }
+
+        println!(
+            "{}",
+            format_output_line(&[
+                "PID".bold().to_string(),
+                "PPID".bold().to_string(),
+                "USER".bold().to_string(),
+                "STIME".bold().to_string(),
+                "RSS".bold().to_string(),
+                "TIME".bold().to_string(),
+                "COMMAND".bold().to_string(),
+            ])
+        );
+
+        for proc in procs {
+            println!(
+                "{}",
+                format_output_line(&[
+                    proc.pid.to_string().cyan().to_string(),
+                    proc.ppid.to_string().cyan().to_string(),
+                    username(&users, proc.uid).green().to_string(),
+                    format_start_time(now, proc.start_secs),
+                    util::human_size(proc.rss_kb * 1024),
+                    format_cpu_time(proc.time_secs),
+                    proc.args,
+                ])
+            );
+        }
+    }
+
+    println!();
+
+    Ok(())
+}
+
+/// Format a start time the same way `ps` and `svcs` do: the time if started
+/// within the past 24 hours, the date otherwise.
+fn format_start_time(now: i64, start: i64) -> String {
+    let date = match Local.timestamp_opt(start, 0).single() {
+        Some(date) => date,
+        None => return "-".to_string(),
+    };
+
+    match now - start {
+        n if n < 24 * 60 * 60 => date.format("%H:%M:%S").to_string(),
+        _ => date.format("%b_%d").to_string(),
+    }
+}
+
+/// Format cpu time as `[[dd-]hh:]mm:ss`.
+fn format_cpu_time(secs: i64) -> String {
+    let (days, hours) = (secs / 86400, secs / 3600 % 24);
+    let (minutes, seconds) = (secs / 60 % 60, secs % 60);
+
+    match (days, hours) {
+        (0, 0) => format!("{}:{:02}", minutes, seconds),
+        (0, _) => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+        _ => format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds),
+    }
+}
+
+fn format_output_line<T: AsRef<str>>(cols: &[T]) -> String {
+    let data = [
+        (cols[0].as_ref(), 7),
+        (cols[1].as_ref(), 7),
+        (cols[2].as_ref(), 10),
+        (cols[3].as_ref(), 9),
+        (cols[4].as_ref(), 7),
+        (cols[5].as_ref(), 9),
+        (cols[6].as_ref(), 0),
+    ];
+
+    let mut line = String::new();
+
+    for (text, max) in data {
+        let cas = ColorAwareString::with_string(text.into());
+
+        line.push(' ');
+        let padded = cas.pad_end(max);
+        line.push_str(&padded);
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_start_time() {
+        let start = 1696852800;
+        let date = Local.timestamp_opt(start, 0).unwrap();
+        let time = date.format("%H:%M:%S").to_string();
+        let day = date.format("%b_%d").to_string();
+
+        let tests = [
+            (0, &time),
+            (59, &time),
+            (2 * 60 * 60, &time),
+            (24 * 60 * 60 - 1, &time),
+            (24 * 60 * 60, &day),
+            (3 * 24 * 60 * 60, &day),
+        ];
+
+        for (ago, want) in tests {
+            assert_eq!(&format_start_time(start + ago, start), want, "{}", ago);
+        }
+    }
+
+    #[test]
+    fn test_format_cpu_time() {
+        let tests = [
+            (0, "0:00"),
+            (59, "0:59"),
+            (61, "1:01"),
+            (59 * 60 + 59, "59:59"),
+            (60 * 60, "1:00:00"),
+            (60 * 60 + 2 * 60 + 3, "1:02:03"),
+            (24 * 60 * 60, "1-00:00:00"),
+            (2 * 24 * 60 * 60 + 3 * 60 * 60 + 4 * 60 + 5, "2-03:04:05"),
+        ];
+
+        for (secs, want) in tests {
+            assert_eq!(format_cpu_time(secs), want, "{}", secs);
+        }
+    }
+}
@@ -1,139 +1,213 @@
 //! `smf status ...`
 
 use std::cmp;
+use std::error::Error;
+use std::fmt;
 
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use colored::*;
 use indexmap::map::IndexMap;
 use libcontract::status::{ContractStatus, Detail};
-use smf::{Query, QuerySelection};
+use smf::{Query, SmfState, SvcStatus};
 
 use crate::util;
 use util::color_aware_string::ColorAwareString;
 use util::smf::{
-    stylize_smf_date, stylize_smf_fmri, stylize_smf_state_full,
-    stylize_smf_state_small,
+    find_status_matching, get_status_matching, stylize_smf_date,
+    stylize_smf_fmri, stylize_smf_state_full, stylize_smf_state_small,
 };
 
 use crate::arguments::SubCommandStatus;
 
+/**
+ * Why `smf status` is exiting non-zero even though nothing went wrong (so it
+ * can be used as a health check in scripts).
+ *
+ * Ordered from least to most severe; only the worst one is reported.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Unhealthy {
+    /// Some services are not online.
+    NotOnline,
+    /// Some patterns didn't match any services.
+    NoMatch,
+    /// Some services are in maintenance.
+    Maintenance,
+}
+
+impl Unhealthy {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Unhealthy::NotOnline => 3,
+            Unhealthy::NoMatch => 4,
+            Unhealthy::Maintenance => 5,
+        }
+    }
+}
+
+impl fmt::Display for Unhealthy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Unhealthy::NotOnline => "some services are not online",
+            Unhealthy::NoMatch => "some patterns matched no services",
+            Unhealthy::Maintenance => "some services are in maintenance",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl Error for Unhealthy {}
+
 pub fn run(cmd: SubCommandStatus) -> Result<()> {
-    let select = QuerySelection::ByPattern(&cmd.services);
     let q = Query::new();
-    let svcs = q
-        .get_status(select)
-        .with_context(|| format!("failed to get_status: {:?}", cmd.services))?;
+    let (svcs, unmatched) = match cmd.quiet {
+        true => find_status_matching(&q, &cmd.services)?,
+        false => get_status_matching(&q, &cmd.services)?,
+    };
 
     let now = Utc::now().naive_utc();
 
     // loop each service to process
-    for (i, svc) in svcs.enumerate() {
+    let mut unhealthy = match unmatched.is_empty() {
+        true => None,
+        false => Some(Unhealthy::NoMatch),
+    };
+    for (i, svc) in svcs.iter().enumerate() {
+        unhealthy = cmp::max(unhealthy, health(&svc.state));
+
+        if cmd.quiet {
+            continue;
+        }
+
         if i > 0 {
             println!();
         }
 
-        let mut map = IndexMap::new();
-        let cur_svc = &[&svc.fmri];
-
-        // gather service data
-        let dependencies: Vec<_> = q
-            .get_dependencies_of(cur_svc)
-            .with_context(|| {
-                format!("failed to get_dependencies_of: {}", &svc.fmri)
-            })?
-            .collect();
-        let dependents: Vec<_> = q
-            .get_dependents_of(cur_svc)
-            .with_context(|| {
-                format!("failed to get_dependents_of: {}", &svc.fmri)
-            })?
-            .collect();
-        let log_files: Vec<_> = q
-            .get_log_files(cur_svc)
-            .with_context(|| format!("failed to get_log_files: {}", &svc.fmri))?
-            .collect();
-
-        // format and store output
-        let fmri = stylize_smf_fmri(&svc.fmri)?;
-        let state_full = stylize_smf_state_full(&svc.state);
-        let state_small = stylize_smf_state_small(&svc.state);
-        let time = stylize_smf_date(&now, &svc.service_time)?;
-
-        map.insert("fmri", format!("{} {}", state_small, fmri));
-        map.insert("state", state_full);
-        map.insert("description", svc.description.unwrap_or_default());
-        map.insert("time", time);
-        map.insert("zone", svc.zone.green().to_string());
-
-        // gather contract data (if applicable)
-        if let Some(ctid) = svc.contract_id {
-            let ctid = ctid as u32;
-            map.insert("contract", ctid.to_string().magenta().to_string());
-
-            if let Ok(status) = ContractStatus::new(ctid, Detail::All) {
-                let members = status.get_members().unwrap_or_default();
-                if !members.is_empty() {
-                    let pids: Vec<_> = members
-                        .iter()
-                        .map(|x| x.to_string().cyan().to_string())
-                        .collect();
-                    map.insert("pids", pids.join(", "));
-                }
-            };
-        }
+        let map = get_status_map(&q, &now, svc, cmd.long)?;
 
-        // format deps and other multi-line data
-        {
-            let mut s = vec![];
-            s.push(dependencies.len().to_string().magenta().to_string());
+        // format the output for the current service and print it
+        let s = format_status_map(&map);
+        println!("{}", s);
+    }
 
-            if cmd.long {
-                for dep in dependencies {
-                    let dep_fmri = stylize_smf_fmri(&dep.fmri)?;
-                    let dep_state_small = stylize_smf_state_small(&dep.state);
-                    s.push(format!("{} {}", dep_state_small, dep_fmri));
-                }
+    match unhealthy {
+        Some(unhealthy) => Err(unhealthy.into()),
+        None => Ok(()),
+    }
+}
+
+/// Check whether a service in the given state counts as healthy.
+fn health(state: &SmfState) -> Option<Unhealthy> {
+    match state {
+        SmfState::Online | SmfState::Legacy => None,
+        SmfState::Maintenance => Some(Unhealthy::Maintenance),
+        _ => Some(Unhealthy::NotOnline),
+    }
+}
+
+/// Gather the data shown by `smf status` for a single service.
+pub fn get_status_map(
+    q: &Query,
+    now: &NaiveDateTime,
+    svc: &SvcStatus,
+    long: bool,
+) -> Result<IndexMap<&'static str, String>> {
+    let mut map = IndexMap::new();
+    let cur_svc = &[&svc.fmri];
+
+    // gather service data
+    let dependencies: Vec<_> = q
+        .get_dependencies_of(cur_svc)
+        .with_context(|| {
+            format!("failed to get_dependencies_of: {}", &svc.fmri)
+        })?
+        .collect();
+    let dependents: Vec<_> = q
+        .get_dependents_of(cur_svc)
+        .with_context(|| format!("failed to get_dependents_of: {}", &svc.fmri))?
+        .collect();
+    let log_files: Vec<_> = q
+        .get_log_files(cur_svc)
+        .with_context(|| format!("failed to get_log_files: {}", &svc.fmri))?
+        .collect();
+
+    // format and store output
+    let fmri = stylize_smf_fmri(&svc.fmri)?;
+    let state_full = stylize_smf_state_full(&svc.state);
+    let state_small = stylize_smf_state_small(&svc.state);
+    let time = stylize_smf_date(now, &svc.service_time)?;
+
+    map.insert("fmri", format!("{} {}", state_small, fmri));
+    map.insert("state", state_full);
+    map.insert("description", svc.description.clone().unwrap_or_default());
+    map.insert("time", time);
+    map.insert("zone", svc.zone.green().to_string());
+
+    // gather contract data (if applicable)
+    if let Some(ctid) = svc.contract_id {
+        let ctid = ctid as u32;
+        map.insert("contract", ctid.to_string().magenta().to_string());
+
+        if let Ok(status) = ContractStatus::new(ctid, Detail::All) {
+            let members = status.get_members().unwrap_or_default();
+            if !members.is_empty() {
+                let pids: Vec<_> = members
+                    .iter()
+                    .map(|x| x.to_string().cyan().to_string())
+                    .collect();
+                map.insert("pids", pids.join(", "));
             }
-            map.insert("dependencies", s.join("\n"));
-        }
+        };
+    }
 
-        {
-            let mut s = vec![];
-            s.push(dependents.len().to_string().magenta().to_string());
+    // format deps and other multi-line data
+    {
+        let mut s = vec![];
+        s.push(dependencies.len().to_string().magenta().to_string());
 
-            if cmd.long {
-                for dep in dependents {
-                    let dep_fmri = stylize_smf_fmri(&dep.fmri)?;
-                    let dep_state_small = stylize_smf_state_small(&dep.state);
-                    s.push(format!("{} {}", dep_state_small, dep_fmri));
-                }
+        if long {
+            for dep in dependencies {
+                let dep_fmri = stylize_smf_fmri(&dep.fmri)?;
+                let dep_state_small = stylize_smf_state_small(&dep.state);
+                s.push(format!("{} {}", dep_state_small, dep_fmri));
             }
-            map.insert("dependents", s.join("\n"));
         }
+        map.insert("dependencies", s.join("\n"));
+    }
 
-        {
-            let mut s = vec![];
-            s.push(log_files.len().to_string().magenta().to_string());
+    {
+        let mut s = vec![];
+        s.push(dependents.len().to_string().magenta().to_string());
 
-            if cmd.long {
-                for log_file in log_files {
-                    let name = log_file.into_os_string().into_string().unwrap();
-                    s.push(name.cyan().to_string());
-                }
+        if long {
+            for dep in dependents {
+                let dep_fmri = stylize_smf_fmri(&dep.fmri)?;
+                let dep_state_small = stylize_smf_state_small(&dep.state);
+                s.push(format!("{} {}", dep_state_small, dep_fmri));
             }
-            map.insert("log files", s.join("\n"));
         }
+        map.insert("dependents", s.join("\n"));
+    }
 
-        // format the output for the current service and print it
-        let s = format_status_map(&map);
-        println!("{}", s);
+    {
+        let mut s = vec![];
+        s.push(log_files.len().to_string().magenta().to_string());
+
+        if long {
+            for log_file in log_files {
+                let name = log_file.into_os_string().into_string().unwrap();
+                s.push(name.cyan().to_string());
+            }
+        }
+        map.insert("log files", s.join("\n"));
     }
 
-    Ok(())
+    Ok(map)
 }
 
-fn format_status_map(map: &IndexMap<&str, String>) -> String {
+pub fn format_status_map(map: &IndexMap<&str, String>) -> String {
     // figure out which key has the max length
     let max_key = map.keys().map(|k| k.len()).reduce(cmp::max).unwrap();
 
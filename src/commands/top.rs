@@ -0,0 +1,277 @@
+//! `smf top ...`
+
+use std::cmp::Reverse;
+use std::io::{self, IsTerminal};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use colored::*;
+use smf::{Query, QuerySelection, SmfState};
+
+use crate::util;
+use util::color_aware_string::ColorAwareString;
+use util::proc::ProcReader;
+use util::smf::{
+    get_contract_members, get_status_matching, stylize_smf_fmri,
+    stylize_smf_state_small,
+};
+
+use crate::arguments::{SubCommandTop, TopSortItems};
+
+/// Resource usage summed across every process in a service's contract.
+struct ServiceUsage {
+    fmri: String,
+    state: SmfState,
+    ctid: usize,
+    procs: usize,
+    cpu: f64,
+    mem: f64,
+    rss_kb: u64,
+}
+
+pub fn run(cmd: SubCommandTop) -> Result<()> {
+    let reader = ProcReader::new();
+    let interval = Duration::from_secs(cmd.interval);
+    let clear = io::stdout().is_terminal();
+
+    // resolve the patterns once instead of on every refresh
+    let mut fmris = vec![];
+    if !cmd.services.is_empty() {
+        let (svcs, _) = get_status_matching(&Query::new(), &cmd.services)?;
+        if svcs.is_empty() {
+            bail!("no services found for: {:?}", cmd.services);
+        }
+        fmris.extend(svcs.into_iter().map(|svc| svc.fmri));
+    }
+
+    let mut i = 0;
+    loop {
+        let usages = get_usages(&reader, &fmris)?;
+
+        // clear the screen and move the cursor to the top left
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        print_usages(usages, &cmd)?;
+
+        i += 1;
+        if cmd.count.is_some_and(|count| i >= count) {
+            break;
+        }
+
+        thread::sleep(interval);
+    }
+
+    Ok(())
+}
+
+/// Get the usage of the services given (or every service if there are none).
+fn get_usages(
+    reader: &ProcReader,
+    fmris: &[String],
+) -> Result<Vec<ServiceUsage>> {
+    let q = Query::new();
+    let svcs: Vec<_> = match fmris.is_empty() {
+        true => {
+            q.get_status_all().context("failed to list services")?.collect()
+        }
+        false => q
+            .get_status(QuerySelection::ByPattern(fmris))
+            .with_context(|| format!("failed to get_status: {:?}", fmris))?
+            .collect(),
+    };
+
+    let mut usages = vec![];
+    for svc in svcs {
+        // only services with a contract have processes
+        let ctid = match svc.contract_id {
+            Some(ctid) => ctid,
+            None => continue,
+        };
+
+        let mut usage = ServiceUsage {
+            fmri: svc.fmri,
+            state: svc.state,
+            ctid,
+            procs: 0,
+            cpu: 0.0,
+            mem: 0.0,
+            rss_kb: 0,
+        };
+
+        let members = get_contract_members(ctid).unwrap_or_default();
+        for proc in members.into_iter().filter_map(|pid| reader.read(pid).ok())
+        {
+            usage.procs += 1;
+            usage.cpu += proc.cpu_percent();
+            usage.mem += proc.mem_percent();
+            usage.rss_kb += proc.rss_kb;
+        }
+
+        usages.push(usage);
+    }
+
+    Ok(usages)
+}
+
+/// Sort services by the field given (biggest first) and keep the top ones.
+fn sort_usages(
+    mut usages: Vec<ServiceUsage>,
+    sort: &TopSortItems,
+    limit: Option<usize>,
+) -> Vec<ServiceUsage> {
+    match sort {
+        TopSortItems::Cpu => usages.sort_by(|a, b| b.cpu.total_cmp(&a.cpu)),
+        TopSortItems::Mem => usages.sort_by(|a, b| b.mem.total_cmp(&a.mem)),
+        TopSortItems::Rss => usages.sort_by_key(|u| Reverse(u.rss_kb)),
+        TopSortItems::Procs => usages.sort_by_key(|u| Reverse(u.procs)),
+        TopSortItems::Fmri => usages.sort_by(|a, b| a.fmri.cmp(&b.fmri)),
+    }
+
+    if let Some(limit) = limit {
+        usages.truncate(limit);
+    }
+
+    usages
+}
+
+fn print_usages(usages: Vec<ServiceUsage>, cmd: &SubCommandTop) -> Result<()> {
+    let total_cpu: f64 = usages.iter().map(|u| u.cpu).sum();
+    let total_rss: u64 = usages.iter().map(|u| u.rss_kb).sum();
+
+    println!(
+        "{} - {} services, {} cpu, {} rss (every {}s)",
+        Local::now().format("%H:%M:%S").to_string().bold(),
+        usages.len().to_string().magenta(),
+        format!("{:.1}%", total_cpu).green(),
+        util::human_size(total_rss * 1024).green(),
+        cmd.interval,
+    );
+    println!();
+    println!(
+        "{}",
+        format_output_line(&[
+            "".bold().to_string(),
+            "SERVICE".bold().to_string(),
+            "CTID".bold().to_string(),
+            "#PIDS".bold().to_string(),
+            "CPU".bold().to_string(),
+            "MEM".bold().to_string(),
+            "RSS".bold().to_string(),
+        ])
+    );
+
+    for usage in sort_usages(usages, &cmd.sort, cmd.limit) {
+        let state = stylize_smf_state_small(&usage.state);
+        let fmri = stylize_smf_fmri(&usage.fmri)?;
+        let ctid = usage.ctid.to_string().magenta().to_string();
+        let procs = match usage.procs {
+            0 => "0".yellow(),
+            n => n.to_string().green(),
+        }
+        .to_string();
+
+        println!(
+            "{}",
+            format_output_line(&[
+                state,
+                fmri,
+                ctid,
+                procs,
+                stylize_percent(usage.cpu),
+                stylize_percent(usage.mem),
+                util::human_size(usage.rss_kb * 1024),
+            ])
+        );
+    }
+
+    Ok(())
+}
+
+fn stylize_percent(pct: f64) -> String {
+    let s = format!("{:.1}%", pct);
+
+    match pct {
+        n if n >= 50.0 => s.red().bold(),
+        n if n >= 10.0 => s.yellow(),
+        n if n > 0.0 => s.normal(),
+        _ => s.black().bold(),
+    }
+    .to_string()
+}
+
+fn format_output_line<T: AsRef<str>>(cols: &[T]) -> String {
+    let data = [
+        (cols[0].as_ref(), 1),
+        (cols[1].as_ref(), 40),
+        (cols[2].as_ref(), 7),
+        (cols[3].as_ref(), 6),
+        (cols[4].as_ref(), 7),
+        (cols[5].as_ref(), 7),
+        (cols[6].as_ref(), 8),
+    ];
+
+    let mut line = String::new();
+
+    for (text, max) in data {
+        let cas = ColorAwareString::with_string(text.into());
+
+        line.push(' ');
+        let padded = cas.pad_end(max);
+        line.push_str(&padded);
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(fmri: &str, procs: usize, cpu: f64, rss_kb: u64) -> ServiceUsage {
+        ServiceUsage {
+            fmri: fmri.to_string(),
+            state: SmfState::Online,
+            ctid: 1,
+            procs,
+            cpu,
+            mem: cpu / 2.0,
+            rss_kb,
+        }
+    }
+
+    #[test]
+    fn test_sort_usages() {
+        let tests = [
+            (TopSortItems::Cpu, None, vec!["svc:/b", "svc:/c", "svc:/a"]),
+            (TopSortItems::Mem, Some(2), vec!["svc:/b", "svc:/c"]),
+            (TopSortItems::Rss, None, vec!["svc:/c", "svc:/a", "svc:/b"]),
+            (TopSortItems::Procs, Some(1), vec!["svc:/a"]),
+            (TopSortItems::Fmri, Some(5), vec!["svc:/a", "svc:/b", "svc:/c"]),
+            (TopSortItems::Fmri, Some(0), vec![]),
+        ];
+
+        for (sort, limit, want) in tests {
+            let usages = vec![
+                usage("svc:/b", 2, 12.5, 1024),
+                usage("svc:/a", 9, 0.0, 2048),
+                usage("svc:/c", 1, 3.0, 4096),
+            ];
+
+            let fmris: Vec<_> = sort_usages(usages, &sort, limit)
+                .into_iter()
+                .map(|u| u.fmri)
+                .collect();
+            assert_eq!(fmris, want, "{:?} {:?}", sort, limit);
+        }
+    }
+
+    #[test]
+    fn test_stylize_percent() {
+        assert!(stylize_percent(0.0).contains("0.0%"));
+        assert!(stylize_percent(12.34).contains("12.3%"));
+        assert!(stylize_percent(99.96).contains("100.0%"));
+    }
+}
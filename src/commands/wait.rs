@@ -0,0 +1,178 @@
+//! `smf wait ...`
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use colored::*;
+use indexmap::map::IndexMap;
+use smf::{Query, SmfState, SvcStatus};
+
+use crate::util;
+use util::deps::state_name;
+use util::smf::{
+    find_status_matching, get_status_matching, stylize_smf_fmri,
+    stylize_smf_state_full, stylize_smf_state_small,
+};
+
+use crate::arguments::{SubCommandWait, WaitState};
+
+pub fn run(cmd: SubCommandWait) -> Result<()> {
+    let q = Query::new();
+    let target = smf_state(&cmd.state);
+    let interval = Duration::from_secs(cmd.interval);
+    let timeout = match cmd.timeout {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let start = Instant::now();
+
+    let (svcs, unmatched) = get_status_matching(&q, &cmd.services)?;
+    if !unmatched.is_empty() {
+        bail!("failed to find service(s): {:?}", unmatched);
+    }
+
+    // the services still being waited on (with their last known status)
+    let mut pending: IndexMap<String, SvcStatus> =
+        svcs.into_iter().map(|svc| (svc.fmri.clone(), svc)).collect();
+
+    let total = pending.len();
+    println!(
+        "waiting for {} service(s) to be {}",
+        total.to_string().magenta(),
+        stylize_smf_state_full(&target)
+    );
+
+    loop {
+        let (svcs, _) = find_status_matching(&q, &cmd.services)?;
+
+        // a service that was deleted will never get there
+        if let Some(fmri) = pending
+            .keys()
+            .find(|fmri| !svcs.iter().any(|svc| &svc.fmri == *fmri))
+        {
+            print_pending(&pending);
+            bail!("{} no longer exists", fmri);
+        }
+
+        for svc in svcs {
+            if !pending.contains_key(&svc.fmri) {
+                continue;
+            }
+
+            if svc.state != target {
+                // services in maintenance (or disabled) won't get there by
+                // themselves so there is no point in waiting for them
+                let stuck = is_stuck(&svc.state, &svc.next_state);
+                let fmri = svc.fmri.clone();
+                pending.insert(fmri.clone(), svc);
+                if stuck {
+                    print_pending(&pending);
+                    bail!(
+                        "{} won't be {} by itself ({})",
+                        fmri,
+                        state_name(&target),
+                        state_name(&pending[&fmri].state)
+                    );
+                }
+                continue;
+            }
+
+            pending.shift_remove(&svc.fmri);
+            let done = total - pending.len();
+            println!(
+                "{} {} is {} {}",
+                stylize_smf_state_small(&svc.state),
+                fmri_name(&svc.fmri),
+                stylize_smf_state_full(&svc.state),
+                format!("({}/{})", done, total).black().bold()
+            );
+        }
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        // don't sleep past the timeout
+        let mut sleep = interval;
+        if let Some(timeout) = timeout {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                break;
+            }
+            sleep = sleep.min(timeout - elapsed);
+        }
+
+        thread::sleep(sleep);
+    }
+
+    print_pending(&pending);
+    bail!(
+        "timed out after {}s waiting for {} of {} service(s)",
+        cmd.timeout,
+        pending.len(),
+        total
+    );
+}
+
+/// Check if a service is stuck in a state it won't leave by itself.
+fn is_stuck(state: &SmfState, next_state: &Option<SmfState>) -> bool {
+    next_state.is_none()
+        && matches!(state, SmfState::Maintenance | SmfState::Disabled)
+}
+
+/// Print the services still being waited on.
+fn print_pending(pending: &IndexMap<String, SvcStatus>) {
+    println!();
+    println!("still waiting on:");
+    for svc in pending.values() {
+        println!(
+            "  {} {} ({})",
+            stylize_smf_state_small(&svc.state),
+            fmri_name(&svc.fmri),
+            stylize_smf_state_full(&svc.state)
+        );
+    }
+}
+
+fn smf_state(state: &WaitState) -> SmfState {
+    match state {
+        WaitState::Online => SmfState::Online,
+        WaitState::Offline => SmfState::Offline,
+        WaitState::Degraded => SmfState::Degraded,
+        WaitState::Maintenance => SmfState::Maintenance,
+        WaitState::Disabled => SmfState::Disabled,
+    }
+}
+
+fn fmri_name(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stuck() {
+        let tests = [
+            (SmfState::Maintenance, None, true),
+            (SmfState::Disabled, None, true),
+            (SmfState::Maintenance, Some(SmfState::Online), false),
+            (SmfState::Disabled, Some(SmfState::Offline), false),
+            (SmfState::Offline, None, false),
+            (SmfState::Online, None, false),
+            (SmfState::Degraded, None, false),
+        ];
+
+        for (state, next_state, want) in tests {
+            assert_eq!(
+                is_stuck(&state, &next_state),
+                want,
+                "{} -> {:?}",
+                state_name(&state),
+                next_state.as_ref().map(state_name)
+            );
+        }
+    }
+}
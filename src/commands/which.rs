@@ -0,0 +1,71 @@
+//! `smf which ...`
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use colored::*;
+use indexmap::map::IndexMap;
+use smf::Query;
+
+use crate::commands::status::{format_status_map, get_status_map};
+use crate::util::proc::ProcReader;
+
+use crate::arguments::SubCommandWhich;
+
+pub fn run(cmd: SubCommandWhich) -> Result<()> {
+    let q = Query::new();
+    let svcs: Vec<_> =
+        q.get_status_all().context("failed to list services")?.collect();
+
+    let now = Utc::now().naive_utc();
+    let reader = ProcReader::new();
+
+    let mut missing = vec![];
+
+    // loop each pid to process
+    for (i, pid) in cmd.pids.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let proc = match reader.read(*pid) {
+            Ok(proc) => proc,
+            Err(err) => {
+                eprintln!("{}: {:#}", pid.to_string().cyan(), err);
+                missing.push(*pid);
+                continue;
+            }
+        };
+
+        // find the service that owns the contract the process is in
+        let ctid = proc.contract as usize;
+        let svc = match svcs.iter().find(|s| s.contract_id == Some(ctid)) {
+            Some(svc) => svc,
+            None => {
+                eprintln!(
+                    "{}: contract {} does not belong to a service",
+                    pid.to_string().cyan(),
+                    ctid.to_string().magenta()
+                );
+                missing.push(*pid);
+                continue;
+            }
+        };
+
+        let mut map = IndexMap::new();
+        map.insert(
+            "process",
+            format!("{} {}", pid.to_string().cyan(), proc.args.black().bold()),
+        );
+        map.extend(get_status_map(&q, &now, svc, cmd.long)?);
+
+        // format the output for the current pid and print it
+        let s = format_status_map(&map);
+        println!("{}", s);
+    }
+
+    if !missing.is_empty() {
+        bail!("no service found for pid(s): {:?}", missing);
+    }
+
+    Ok(())
+}
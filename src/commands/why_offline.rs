@@ -0,0 +1,321 @@
+//! `smf why-offline ...`
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use smf::{Query, SmfState};
+
+use crate::util;
+use util::deps::{is_running, DepGroup, Graph, Grouping};
+use util::smf::{
+    get_status_matching, stylize_smf_fmri, stylize_smf_state_full,
+};
+
+use crate::arguments::SubCommandWhyOffline;
+
+/// An unmet dependency.
+enum Unmet {
+    /// A dependency that won't come online by itself (and why).
+    Root(String, String),
+    /// An offline dependency that has unmet dependencies of its own.
+    Offline(String),
+}
+
+/// A chain of unmet dependencies ending in a root cause.
+#[derive(Clone)]
+struct Cause {
+    chain: Vec<String>,
+    reason: String,
+}
+
+pub fn run(cmd: SubCommandWhyOffline) -> Result<()> {
+    let graph = Graph::load().context("failed to load dependency graph")?;
+
+    // default to every offline service
+    let fmris: Vec<String> = match cmd.services.is_empty() {
+        true => graph
+            .services()
+            .filter(|svc| matches!(svc.state, SmfState::Offline))
+            .map(|svc| svc.fmri.clone())
+            .collect(),
+        false => get_status_matching(&Query::new(), &cmd.services)?
+            .0
+            .into_iter()
+            .map(|svc| svc.fmri)
+            .collect(),
+    };
+
+    if fmris.is_empty() {
+        println!("{} no offline services", "✔".green());
+        return Ok(());
+    }
+
+    let mut finder = CauseFinder { graph: &graph, found: HashMap::new() };
+    let mut unknown = vec![];
+    for (i, fmri) in fmris.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let svc = match graph.get(fmri) {
+            Some(svc) => svc,
+            None => {
+                eprintln!("{} not found in dependency graph", fmri);
+                unknown.push(fmri);
+                continue;
+            }
+        };
+
+        let state = stylize_smf_state_full(&svc.state);
+        println!("{} is {}", fmri_name(fmri), state);
+        if !matches!(svc.state, SmfState::Offline) {
+            continue;
+        }
+
+        // the service itself isn't a cause
+        let causes: Vec<_> = finder
+            .find_causes(fmri, &mut vec![])
+            .into_iter()
+            .filter(|cause| cause.chain.len() > 1)
+            .collect();
+
+        if causes.is_empty() {
+            println!("  no unmet dependencies (the service may be starting)");
+        }
+        for cause in causes {
+            let names: Vec<_> =
+                cause.chain.iter().map(|f| fmri_name(f)).collect();
+            let arrow = " → ".black().bold().to_string();
+            println!("  {} ({})", names.join(&arrow), cause.reason);
+        }
+    }
+
+    if !unknown.is_empty() {
+        bail!("failed to find service(s): {:?}", unknown);
+    }
+
+    Ok(())
+}
+
+struct CauseFinder<'a> {
+    graph: &'a Graph,
+    /// The causes already found for every service (starting at it).
+    found: HashMap<String, Vec<Cause>>,
+}
+
+impl CauseFinder<'_> {
+    /**
+     * Walk the unmet dependencies of an offline service until reaching
+     * services that won't come online by themselves (disabled, in
+     * maintenance, missing, etc.).
+     *
+     * Offline services without any unmet dependencies are root causes as
+     * well (including `fmri` itself).  Every root cause is only reported
+     * once, through the shortest chain leading to it.
+     */
+    fn find_causes(
+        &mut self,
+        fmri: &str,
+        path: &mut Vec<String>,
+    ) -> Vec<Cause> {
+        if let Some(causes) = self.found.get(fmri) {
+            return causes.clone();
+        }
+
+        let graph = self.graph;
+        let svc = match graph.get(fmri) {
+            Some(svc) => svc,
+            None => return vec![],
+        };
+
+        path.push(fmri.to_string());
+        let mut causes = vec![];
+
+        for group in &svc.deps {
+            for unmet in unmet_dependencies(graph, group) {
+                match unmet {
+                    Unmet::Root(dep, reason) => {
+                        let chain = vec![fmri.to_string(), dep];
+                        causes.push(Cause { chain, reason });
+                    }
+                    Unmet::Offline(dep) if path.contains(&dep) => {
+                        let chain = vec![fmri.to_string(), dep];
+                        let reason = "dependency cycle".red().to_string();
+                        causes.push(Cause { chain, reason });
+                    }
+                    Unmet::Offline(dep) => {
+                        for mut cause in self.find_causes(&dep, path) {
+                            cause.chain.insert(0, fmri.to_string());
+                            causes.push(cause);
+                        }
+                    }
+                }
+            }
+        }
+
+        // an offline service that is only waiting on itself
+        if causes.is_empty() {
+            let reason = stylize_smf_state_full(&svc.state);
+            causes.push(Cause { chain: vec![fmri.to_string()], reason });
+        }
+
+        // the same root cause is often reached through many dependencies
+        causes.sort_by_key(|cause| cause.chain.len());
+        let mut roots = vec![];
+        causes.retain(|cause| {
+            let root = cause.chain.last().cloned();
+            match roots.contains(&root) {
+                true => false,
+                false => {
+                    roots.push(root);
+                    true
+                }
+            }
+        });
+
+        path.pop();
+        self.found.insert(fmri.to_string(), causes.clone());
+
+        causes
+    }
+}
+
+/// Get the dependencies of a group that keep the service offline.
+fn unmet_dependencies(graph: &Graph, group: &DepGroup) -> Vec<Unmet> {
+    let required =
+        matches!(group.grouping, Grouping::RequireAll | Grouping::RequireAny);
+    let mut satisfied = false;
+    let mut unmet = vec![];
+
+    for target in &group.targets {
+        if target.is_file() {
+            match target.state == "online" {
+                true => satisfied = true,
+                false if required => {
+                    let reason = target.state.red().to_string();
+                    unmet.push(Unmet::Root(target.fmri.clone(), reason));
+                }
+                false => (),
+            }
+            continue;
+        }
+
+        let resolved = graph.resolve(&target.fmri);
+        if resolved.is_empty() && required {
+            let reason = "absent".red().to_string();
+            unmet.push(Unmet::Root(target.fmri.clone(), reason));
+        }
+
+        // a single running instance of a service is enough (except for
+        // `exclude_all` where every one of them counts)
+        let met = resolved.iter().any(|dep| is_running(&dep.state));
+        satisfied |= met;
+        if met && group.grouping != Grouping::ExcludeAll {
+            continue;
+        }
+
+        for dep in resolved {
+            let running = is_running(&dep.state);
+
+            let offline = matches!(dep.state, SmfState::Offline);
+            match group.grouping {
+                Grouping::ExcludeAll if running => {
+                    let reason = format!(
+                        "{} {}",
+                        stylize_smf_state_full(&dep.state),
+                        group.grouping.to_string().black().bold()
+                    );
+                    unmet.push(Unmet::Root(dep.fmri.clone(), reason));
+                }
+                Grouping::ExcludeAll => (),
+                // optional dependencies only matter if they may come online
+                Grouping::OptionalAll if offline => {
+                    unmet.push(Unmet::Offline(dep.fmri.clone()))
+                }
+                Grouping::OptionalAll => (),
+                _ if running => (),
+                _ if offline => unmet.push(Unmet::Offline(dep.fmri.clone())),
+                _ => {
+                    let reason = stylize_smf_state_full(&dep.state);
+                    unmet.push(Unmet::Root(dep.fmri.clone(), reason));
+                }
+            }
+        }
+    }
+
+    // a single running service is enough for `require_any`
+    if group.grouping == Grouping::RequireAny && satisfied {
+        return vec![];
+    }
+
+    unmet
+}
+
+fn fmri_name(fmri: &str) -> String {
+    stylize_smf_fmri(fmri).unwrap_or_else(|_| fmri.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_causes() {
+        let mut s = String::new();
+        for (fmri, state, deps) in [
+            ("svc:/a:default", "offline", "svc:/b svc:/c"),
+            ("svc:/b:default", "offline", "svc:/d:default"),
+            ("svc:/c:default", "offline", "svc:/d:default svc:/e:default"),
+            ("svc:/d:default", "maintenance", ""),
+            ("svc:/e:default", "offline", "svc:/c:default"),
+            ("svc:/f:default", "offline", "svc:/g svc:/h"),
+            ("svc:/g:a", "online", ""),
+            ("svc:/g:b", "disabled", ""),
+            ("svc:/h:a", "disabled", ""),
+            ("svc:/h:b", "offline", "svc:/d:default"),
+        ] {
+            let prefix = format!("{}/:properties", fmri);
+            s += &format!("{}/restarter/state astring {}\n", prefix, state);
+            for (i, dep) in deps.split_whitespace().enumerate() {
+                s += &format!(
+                    "{p}/dep{i}/grouping astring require_all\n\
+                     {p}/dep{i}/restart_on astring none\n\
+                     {p}/dep{i}/entities fmri {}\n",
+                    dep,
+                    p = prefix,
+                    i = i
+                );
+            }
+        }
+        let graph = Graph::parse(&s).unwrap();
+        let mut finder = CauseFinder { graph: &graph, found: HashMap::new() };
+
+        let causes = finder.find_causes("svc:/a:default", &mut vec![]);
+        let chains: Vec<_> = causes.iter().map(|c| c.chain.join(" ")).collect();
+        assert_eq!(
+            chains,
+            [
+                "svc:/a:default svc:/b:default svc:/d:default",
+                "svc:/a:default svc:/c:default svc:/e:default svc:/c:default",
+            ]
+        );
+
+        // one running instance of `svc:/g` is enough but neither instance of
+        // `svc:/h` is running
+        let causes = finder.find_causes("svc:/f:default", &mut vec![]);
+        let chains: Vec<_> = causes.iter().map(|c| c.chain.join(" ")).collect();
+        assert_eq!(
+            chains,
+            [
+                "svc:/f:default svc:/h:a",
+                "svc:/f:default svc:/h:b svc:/d:default",
+            ]
+        );
+
+        // already found while looking at `svc:/a:default`
+        assert!(finder.found.contains_key("svc:/e:default"));
+        let causes = finder.find_causes("svc:/d:default", &mut vec![]);
+        assert_eq!(causes[0].chain, ["svc:/d:default"]);
+    }
+}
@@ -6,6 +6,8 @@
  * License: MIT
  */
 
+use std::process;
+
 use anyhow::Result;
 
 mod arguments;
@@ -13,15 +15,61 @@ mod commands;
 mod util;
 
 use arguments::SubCommands;
+use util::pager::Pager;
 
 fn main() -> Result<()> {
+    // exit quietly when the reader of our output goes away (ie. `| head`)
+    // instead of failing to print
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+
     let args = arguments::parse();
 
-    match args.command {
+    // only page commands that print everything at once
+    let page = !args.no_pager
+        && match &args.command {
+            SubCommands::Status(cmd) => !cmd.quiet,
+            SubCommands::Log(cmd) => !cmd.follow,
+            _ => false,
+        };
+    let pager = match page {
+        true => Pager::start()?,
+        false => None,
+    };
+
+    let res = match args.command {
+        SubCommands::Blame(cmd) => commands::blame::run(cmd),
+        SubCommands::Check(cmd) => commands::check::run(cmd),
+        SubCommands::Deps(cmd) => commands::deps::run(cmd),
+        SubCommands::Graph(cmd) => commands::graph::run(cmd),
+        SubCommands::Impact(cmd) => commands::impact::run(cmd),
         SubCommands::List(cmd) => commands::list::run(cmd),
         SubCommands::Log(cmd) => commands::log::run(cmd),
+        SubCommands::Milestones(cmd) => commands::milestones::run(cmd),
+        SubCommands::Ps(cmd) => commands::ps::run(cmd),
         SubCommands::Status(cmd) => commands::status::run(cmd),
+        SubCommands::Top(cmd) => commands::top::run(cmd),
+        SubCommands::Wait(cmd) => commands::wait::run(cmd),
+        SubCommands::Which(cmd) => commands::which::run(cmd),
+        SubCommands::WhyOffline(cmd) => commands::why_offline::run(cmd),
+        SubCommands::Kill(cmd) => commands::kill::run(cmd),
         SubCommands::Enable { .. } => commands::enable::run(),
         SubCommands::Disable { .. } => commands::disable::run(),
+    };
+
+    if let Some(pager) = pager {
+        pager.finish()?;
+    }
+
+    // `smf status` uses distinct exit codes for unhealthy services
+    if let Some(unhealthy) = res
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<commands::status::Unhealthy>())
+    {
+        process::exit(unhealthy.exit_code());
     }
+
+    res
 }
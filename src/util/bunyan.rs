@@ -0,0 +1,228 @@
+//! Parsing and pretty-printing of bunyan (JSON) log records.
+//!
+//! See <https://github.com/trentm/node-bunyan#log-record-fields> for the
+//! format of a record.
+
+use colored::*;
+use serde_json::{Map, Value};
+
+/// Fields that are part of every bunyan record (and printed specially).
+const CORE_FIELDS: [&str; 7] =
+    ["v", "level", "name", "hostname", "pid", "time", "msg"];
+
+/// Bunyan log levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace = 10,
+    Debug = 20,
+    Info = 30,
+    Warn = 40,
+    Error = 50,
+    Fatal = 60,
+}
+
+impl Level {
+    /// Get the level for a numeric value (rounding down to the nearest
+    /// known level).
+    pub fn from_value(n: u64) -> Level {
+        match n {
+            n if n >= 60 => Level::Fatal,
+            n if n >= 50 => Level::Error,
+            n if n >= 40 => Level::Warn,
+            n if n >= 30 => Level::Info,
+            n if n >= 20 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    fn stylize(&self) -> String {
+        match self {
+            Level::Trace => "TRACE".white(),
+            Level::Debug => "DEBUG".yellow(),
+            Level::Info => " INFO".cyan(),
+            Level::Warn => " WARN".magenta(),
+            Level::Error => "ERROR".red(),
+            Level::Fatal => "FATAL".reversed(),
+        }
+        .to_string()
+    }
+}
+
+/// Parse a line as a JSON object (returns `None` for anything else).
+pub fn parse_json(line: &str) -> Option<Map<String, Value>> {
+    if !line.starts_with('{') {
+        return None;
+    }
+
+    match serde_json::from_str(line) {
+        Ok(Value::Object(obj)) => Some(obj),
+        _ => None,
+    }
+}
+
+/// Check if a JSON object looks like a bunyan record.
+pub fn is_bunyan_record(obj: &Map<String, Value>) -> bool {
+    obj.get("v").is_some_and(Value::is_u64)
+        && obj.get("level").is_some_and(Value::is_u64)
+        && obj.get("msg").is_some_and(Value::is_string)
+}
+
+/// Get the level of a bunyan record (if it has one).
+pub fn record_level(obj: &Map<String, Value>) -> Option<Level> {
+    obj.get("level").and_then(Value::as_u64).map(Level::from_value)
+}
+
+/// Get the time string of a bunyan record (if it has one).
+pub fn record_time(obj: &Map<String, Value>) -> Option<&str> {
+    obj.get("time").and_then(Value::as_str)
+}
+
+/**
+ * Format a bunyan record the same way the `bunyan` CLI tool does:
+ *
+ * ```text
+ * [2023-10-09T12:00:00.000Z]  INFO: name/1234 on host: msg (key=value)
+ *     other: {
+ *       "nested": true
+ *     }
+ * ```
+ */
+pub fn format_record(obj: &Map<String, Value>) -> String {
+    let time = record_time(obj).unwrap_or("-");
+    let level = match record_level(obj) {
+        Some(level) => level.stylize(),
+        None => "-".to_string(),
+    };
+    let name = obj.get("name").and_then(Value::as_str).unwrap_or("-");
+    let pid = obj.get("pid").map(value_to_string).unwrap_or_default();
+    let hostname = obj.get("hostname").and_then(Value::as_str).unwrap_or("-");
+    let msg = obj.get("msg").and_then(Value::as_str).unwrap_or_default();
+
+    let mut inline = vec![];
+    let mut details = vec![];
+    for (key, value) in obj {
+        if CORE_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+
+        match value {
+            // errors get their stack printed
+            Value::Object(err) if key == "err" && err.contains_key("stack") => {
+                let stack = err.get("stack").map(value_to_string);
+                details.push(indent(&stack.unwrap_or_default()));
+            }
+            // short, simple values are shown on the same line
+            Value::Number(_) | Value::Bool(_) | Value::Null => {
+                inline.push(format!("{}={}", key, value));
+            }
+            Value::String(s)
+                if s.len() < 50 && !s.contains(char::is_whitespace) =>
+            {
+                inline.push(format!("{}={}", key, s));
+            }
+            _ => {
+                let pretty = match value {
+                    Value::String(s) => s.to_string(),
+                    v => serde_json::to_string_pretty(v).unwrap_or_default(),
+                };
+                details.push(indent(&format!("{}: {}", key, pretty)));
+            }
+        }
+    }
+
+    let mut s = format!(
+        "[{}] {}: {}/{} on {}: {}",
+        time.black().bold(),
+        level,
+        name,
+        pid,
+        hostname,
+        msg.cyan()
+    );
+
+    if !inline.is_empty() {
+        s = format!(
+            "{} {}",
+            s,
+            format!("({})", inline.join(", ")).black().bold()
+        );
+    }
+
+    for detail in details {
+        s = format!("{}\n{}", s, detail);
+    }
+
+    s
+}
+
+/// Pretty-print a JSON object with indentation.
+pub fn format_json(obj: &Map<String, Value>) -> String {
+    serde_json::to_string_pretty(obj).unwrap_or_default()
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+fn indent(s: &str) -> String {
+    let lines: Vec<_> = s.lines().map(|line| format!("    {}", line)).collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = r#"{"name":"myapp","hostname":"box","pid":123,"level":40,"msg":"slow request","time":"2023-10-09T12:00:00.000Z","v":0,"route":"/ping","latency":1500}"#;
+
+    #[test]
+    fn test_parse() {
+        let obj = parse_json(RECORD).unwrap();
+        assert!(is_bunyan_record(&obj));
+        assert_eq!(record_level(&obj), Some(Level::Warn));
+        assert_eq!(record_time(&obj), Some("2023-10-09T12:00:00.000Z"));
+
+        let obj = parse_json(r#"{"foo": "bar"}"#).unwrap();
+        assert!(!is_bunyan_record(&obj));
+
+        let invalid = [
+            "",
+            "[ Oct  9 12:00:00 Executing start method (\"a\"). ]",
+            "{ not json",
+            "[1, 2, 3]",
+        ];
+        for line in invalid {
+            assert!(parse_json(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_level() {
+        assert_eq!(Level::from_value(10), Level::Trace);
+        assert_eq!(Level::from_value(35), Level::Info);
+        assert_eq!(Level::from_value(60), Level::Fatal);
+        assert!(Level::Warn > Level::Info);
+    }
+
+    #[test]
+    fn test_format_record() {
+        colored::control::set_override(false);
+
+        let obj = parse_json(RECORD).unwrap();
+        assert_eq!(
+            format_record(&obj),
+            "[2023-10-09T12:00:00.000Z]  WARN: myapp/123 on box: slow request \
+             (route=/ping, latency=1500)"
+        );
+
+        let line = r#"{"name":"a","hostname":"b","pid":1,"level":50,"msg":"oops","time":"t","v":0,"err":{"message":"boom","stack":"Error: boom\n    at foo"}}"#;
+        let obj = parse_json(line).unwrap();
+        assert_eq!(
+            format_record(&obj),
+            "[t] ERROR: a/1 on b: oops\n    Error: boom\n        at foo"
+        );
+    }
+}
@@ -0,0 +1,922 @@
+//! The service dependency graph (as reported by `svcprop`).
+//!
+//! `smf::Query` can list the dependencies of a service but not how they are
+//! grouped (`require_all`, `optional_all`, etc.) or when the dependent is
+//! restarted, so the dependency property groups of every instance are read
+//! with `svcprop -f` instead:
+//!
+//! ```text
+//! svc:/network/ssh:default/:properties/general/enabled boolean true
+//! svc:/network/ssh:default/:properties/restarter/state astring online
+//! svc:/network/ssh:default/:properties/restarter/state_timestamp time 1696852800.123456000
+//! svc:/network/ssh:default/:properties/net-loopback/grouping astring require_any
+//! svc:/network/ssh:default/:properties/net-loopback/restart_on astring error
+//! svc:/network/ssh:default/:properties/net-loopback/entities fmri svc:/network/loopback svc:/network/physical
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime};
+use colored::*;
+use indexmap::map::IndexMap;
+use smf::SmfState;
+
+/// The properties of an instance (by property group and name).
+type Properties = IndexMap<String, HashMap<String, Vec<String>>>;
+
+/// How the dependencies in a group have to be satisfied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    RequireAll,
+    RequireAny,
+    OptionalAll,
+    ExcludeAll,
+}
+
+impl Grouping {
+    fn from_str(s: &str) -> Option<Grouping> {
+        let grouping = match s {
+            "require_all" => Grouping::RequireAll,
+            "require_any" => Grouping::RequireAny,
+            "optional_all" => Grouping::OptionalAll,
+            "exclude_all" => Grouping::ExcludeAll,
+            _ => return None,
+        };
+
+        Some(grouping)
+    }
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Grouping::RequireAll => "require_all",
+            Grouping::RequireAny => "require_any",
+            Grouping::OptionalAll => "optional_all",
+            Grouping::ExcludeAll => "exclude_all",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// When the dependent is stopped (and restarted) because of a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartOn {
+    None,
+    Error,
+    Restart,
+    Refresh,
+}
+
+impl RestartOn {
+    fn from_str(s: &str) -> Option<RestartOn> {
+        let restart_on = match s {
+            "none" => RestartOn::None,
+            "error" => RestartOn::Error,
+            "restart" => RestartOn::Restart,
+            "refresh" => RestartOn::Refresh,
+            _ => return None,
+        };
+
+        Some(restart_on)
+    }
+}
+
+impl fmt::Display for RestartOn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RestartOn::None => "none",
+            RestartOn::Error => "error",
+            RestartOn::Restart => "restart",
+            RestartOn::Refresh => "refresh",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A single dependency (a service or a `file://` path).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepTarget {
+    pub fmri: String,
+    /// The state as shown by `svcs` (ie. `online`, `absent`).
+    pub state: String,
+}
+
+impl DepTarget {
+    /// Check if this is a dependency on a file instead of a service.
+    pub fn is_file(&self) -> bool {
+        self.fmri.starts_with("file:")
+    }
+}
+
+/// A dependency group of a service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepGroup {
+    pub grouping: Grouping,
+    pub restart_on: RestartOn,
+    pub targets: Vec<DepTarget>,
+}
+
+impl DepGroup {
+    /// Format the group like `svcs` does: `require_all/none`.
+    pub fn label(&self) -> String {
+        format!("{}/{}", self.grouping, self.restart_on)
+    }
+}
+
+/// A service instance and its dependencies.
+#[derive(Debug)]
+pub struct Service {
+    pub fmri: String,
+    pub enabled: bool,
+    pub state: SmfState,
+    /// When the service entered its current state (UTC).
+    pub state_time: Option<NaiveDateTime>,
+    pub deps: Vec<DepGroup>,
+    /// Properties that couldn't be parsed (and were skipped).
+    pub invalid: Vec<String>,
+}
+
+impl Service {
+    /**
+     * Build a service from its properties.
+     *
+     * Invalid properties are recorded in `invalid` instead of failing since a
+     * single broken service shouldn't hide the rest of the graph.
+     */
+    fn from_properties(fmri: &str, props: &Properties) -> Service {
+        let get = |pg: &str, prop: &str| {
+            props
+                .get(pg)
+                .and_then(|pg| pg.get(prop))
+                .and_then(|values| values.first())
+                .map(|value| value.as_str())
+        };
+
+        // temporary enable/disable overrides the configured value
+        let enabled = get("general_ovr", "enabled")
+            .or_else(|| get("general", "enabled"))
+            == Some("true");
+
+        let mut invalid = vec![];
+
+        let state = match get("restarter", "state") {
+            Some(state) => parse_state(state).unwrap_or_else(|| {
+                invalid.push(format!("state: {}", state));
+                SmfState::Uninitialized
+            }),
+            None => SmfState::Uninitialized,
+        };
+        let state_time =
+            get("restarter", "state_timestamp").and_then(parse_timestamp);
+
+        let mut deps = vec![];
+        for (name, pg) in props {
+            // dependency property groups are the ones with these properties
+            let (grouping, restart_on, entities) = match (
+                pg.get("grouping").and_then(|v| v.first()),
+                pg.get("restart_on").and_then(|v| v.first()),
+                pg.get("entities"),
+            ) {
+                (Some(grouping), Some(restart_on), Some(entities)) => {
+                    (grouping, restart_on, entities)
+                }
+                _ => continue,
+            };
+
+            let (grouping, restart_on) = match (
+                Grouping::from_str(grouping),
+                RestartOn::from_str(restart_on),
+            ) {
+                (Some(grouping), Some(restart_on)) => (grouping, restart_on),
+                _ => {
+                    invalid.push(format!(
+                        "dependency {}: {}/{}",
+                        name, grouping, restart_on
+                    ));
+                    continue;
+                }
+            };
+            let targets = entities
+                .iter()
+                .map(|fmri| DepTarget {
+                    fmri: fmri.clone(),
+                    state: String::new(),
+                })
+                .collect();
+
+            deps.push(DepGroup { grouping, restart_on, targets });
+        }
+
+        Service {
+            fmri: fmri.to_string(),
+            enabled,
+            state,
+            state_time,
+            deps,
+            invalid,
+        }
+    }
+}
+
+/// Every service instance (keyed by FMRI) and its dependencies.
+#[derive(Debug, Default)]
+pub struct Graph {
+    services: IndexMap<String, Service>,
+    /// The instances of every service (as indexes into `services`).
+    instances: HashMap<String, Vec<usize>>,
+    /// The dependents of every instance (as indexes into `services` and the
+    /// dependency groups of that service).
+    dependents: HashMap<String, Vec<(usize, usize)>>,
+    /// Lines of `svcprop` output that couldn't be parsed (and were skipped).
+    pub invalid: Vec<String>,
+}
+
+impl Graph {
+    /// Load the graph for every service on the system (warning about any
+    /// invalid properties).
+    pub fn load() -> Result<Graph> {
+        let graph = Graph::read()?;
+
+        for line in &graph.invalid {
+            warn(&format!("ignoring invalid svcprop line: {}", line));
+        }
+        for svc in graph.services() {
+            for problem in &svc.invalid {
+                warn(&format!("{}: ignoring invalid {}", svc.fmri, problem));
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Load the graph for every service on the system.
+    pub fn read() -> Result<Graph> {
+        let output = Command::new("svcprop")
+            .args(["-f", "*"])
+            .output()
+            .context("failed to run svcprop")?;
+
+        if !output.status.success() {
+            bail!(
+                "svcprop failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut graph = Graph::parse(&String::from_utf8_lossy(&output.stdout))?;
+        graph.check_files();
+
+        Ok(graph)
+    }
+
+    /// Parse the output of `svcprop -f`.
+    pub fn parse(s: &str) -> Result<Graph> {
+        let mut graph = Graph::default();
+        let mut props: IndexMap<&str, Properties> = IndexMap::new();
+
+        for line in s.lines().filter(|line| !line.is_empty()) {
+            let (name, value) = match line.split_once(' ') {
+                Some((name, rest)) => match rest.split_once(' ') {
+                    Some((_type, value)) => (name, value),
+                    None => (name, ""),
+                },
+                None => (line, ""),
+            };
+
+            let prop = name
+                .split_once("/:properties/")
+                .and_then(|(fmri, prop)| Some((fmri, prop.split_once('/')?)));
+            let (fmri, (pg, prop)) = match prop {
+                Some(prop) => prop,
+                None => {
+                    graph.invalid.push(line.to_string());
+                    continue;
+                }
+            };
+
+            // services (as opposed to instances) can't be running
+            if instance_of(fmri).is_none() {
+                continue;
+            }
+
+            props
+                .entry(fmri)
+                .or_default()
+                .entry(pg.to_string())
+                .or_default()
+                .insert(prop.to_string(), parse_values(value));
+        }
+
+        for (fmri, props) in props {
+            let svc = Service::from_properties(fmri, &props);
+            graph.services.insert(svc.fmri.clone(), svc);
+        }
+        graph.index();
+
+        Ok(graph)
+    }
+
+    /// Build the indexes used by `resolve` and `dependents`.
+    fn index(&mut self) {
+        for (i, fmri) in self.services.keys().enumerate() {
+            if let Some(service) = instance_of(fmri) {
+                self.instances.entry(service.to_string()).or_default().push(i);
+            }
+        }
+
+        let mut dependents: HashMap<String, Vec<(usize, usize)>> =
+            HashMap::new();
+        for (i, svc) in self.services.values().enumerate() {
+            for (j, group) in svc.deps.iter().enumerate() {
+                let targets = group.targets.iter().filter(|t| !t.is_file());
+                for dep in targets.flat_map(|t| self.resolve(&t.fmri)) {
+                    let found = dependents.entry(dep.fmri.clone()).or_default();
+                    if !found.contains(&(i, j)) {
+                        found.push((i, j));
+                    }
+                }
+            }
+        }
+        self.dependents = dependents;
+
+        // fill in the state of every dependency like `svcs` shows it
+        let states: Vec<Vec<Vec<Option<String>>>> = self
+            .services
+            .values()
+            .map(|svc| {
+                svc.deps
+                    .iter()
+                    .map(|group| {
+                        group
+                            .targets
+                            .iter()
+                            .map(|target| match target.is_file() {
+                                true => None,
+                                false => Some(self.target_state(&target.fmri)),
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        for (svc, states) in self.services.values_mut().zip(states) {
+            for (group, states) in svc.deps.iter_mut().zip(states) {
+                for (target, state) in group.targets.iter_mut().zip(states) {
+                    if let Some(state) = state {
+                        target.state = state;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the state of a dependency (a running instance if there are many).
+    fn target_state(&self, fmri: &str) -> String {
+        let resolved = self.resolve(fmri);
+        let svc = resolved
+            .iter()
+            .find(|svc| is_running(&svc.state))
+            .or(resolved.first());
+
+        match svc {
+            Some(svc) => state_name(&svc.state).to_string(),
+            None => "absent".to_string(),
+        }
+    }
+
+    /// Check whether the files depended on exist (like `svcs` does).
+    fn check_files(&mut self) {
+        let targets = self
+            .services
+            .values_mut()
+            .flat_map(|svc| svc.deps.iter_mut())
+            .flat_map(|group| group.targets.iter_mut())
+            .filter(|target| target.is_file());
+
+        for target in targets {
+            let path = target.fmri.trim_start_matches("file://");
+            let path = path.strip_prefix("localhost").unwrap_or(path);
+            target.state = match Path::new(path).exists() {
+                true => "online".to_string(),
+                false => "absent".to_string(),
+            };
+        }
+    }
+
+    /// Iterate over every service instance.
+    pub fn services(&self) -> impl Iterator<Item = &Service> {
+        self.services.values()
+    }
+
+    /// Get a service instance by its full FMRI.
+    pub fn get(&self, fmri: &str) -> Option<&Service> {
+        self.services.get(fmri)
+    }
+
+    /**
+     * Resolve a dependency FMRI to the instances it refers to.
+     *
+     * Dependencies are often declared on a service instead of an instance
+     * (ie. `svc:/milestone/network`) which is met by any of its instances
+     * (like svc.startd does).
+     */
+    pub fn resolve(&self, fmri: &str) -> Vec<&Service> {
+        if let Some(svc) = self.get(fmri) {
+            return vec![svc];
+        }
+
+        self.instances
+            .get(fmri)
+            .into_iter()
+            .flatten()
+            .filter_map(|i| self.services.get_index(*i))
+            .map(|(_, svc)| svc)
+            .collect()
+    }
+
+    /// Get every service (and the dependency group) that depends on `fmri`.
+    pub fn dependents(&self, fmri: &str) -> Vec<(&Service, &DepGroup)> {
+        self.dependents
+            .get(fmri)
+            .into_iter()
+            .flatten()
+            .filter_map(|(i, j)| {
+                let (_, svc) = self.services.get_index(*i)?;
+                Some((svc, svc.deps.get(*j)?))
+            })
+            .collect()
+    }
+
+    /**
+     * Get every service a milestone (transitively) depends on, and whether
+     * the milestone actually needs it to be online.
+     *
+     * Services only reached through `optional_all` dependencies (or
+     * `require_any` groups that are already satisfied, or instances of a
+     * service that already has a running one) are members but can't block
+     * the milestone.
+     */
+    pub fn members(&self, fmri: &str) -> IndexMap<String, bool> {
+        let mut members = IndexMap::new();
+        let mut todo = vec![(fmri.to_string(), true)];
+
+        while let Some((fmri, required)) = todo.pop() {
+            let svc = match self.get(&fmri) {
+                Some(svc) => svc,
+                None => continue,
+            };
+
+            for group in &svc.deps {
+                if group.grouping == Grouping::ExcludeAll {
+                    continue;
+                }
+
+                let targets: Vec<_> = group
+                    .targets
+                    .iter()
+                    .filter(|target| !target.is_file())
+                    .map(|target| self.resolve(&target.fmri))
+                    .collect();
+                let running = |deps: &[&Service]| {
+                    deps.iter().any(|d| is_running(&d.state))
+                };
+                let satisfied = targets.iter().any(|deps| running(deps));
+
+                // any running instance of a service satisfies a dependency on
+                // it so the other instances aren't needed
+                let deps = targets.iter().flat_map(|deps| {
+                    deps.iter().map(|dep| {
+                        let needed = required
+                            && match group.grouping {
+                                Grouping::RequireAll => {
+                                    is_running(&dep.state) || !running(deps)
+                                }
+                                Grouping::RequireAny => !satisfied,
+                                _ => false,
+                            };
+                        (*dep, needed)
+                    })
+                });
+
+                for (dep, needed) in deps {
+                    // revisit a service if it turns out to be required after all
+                    match members.get(&dep.fmri) {
+                        Some(true) => continue,
+                        Some(false) if !needed => continue,
+                        _ => (),
+                    }
+                    members.insert(dep.fmri.clone(), needed);
+                    todo.push((dep.fmri.clone(), needed));
+                }
+            }
+        }
+
+        members
+    }
+
+    /**
+     * Find every dependency cycle (as the FMRIs involved, starting with the
+     * first one in the graph).
+     *
+     * `exclude_all` dependencies and files are ignored since they can't be
+     * part of a cycle.
+     */
+    pub fn find_cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles = vec![];
+        let mut done = HashSet::new();
+        let mut stack = vec![];
+
+        for svc in self.services.values() {
+            self.visit(&svc.fmri, &mut stack, &mut done, &mut cycles);
+        }
+
+        cycles
+    }
+
+    fn visit<'a>(
+        &'a self,
+        fmri: &'a str,
+        stack: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if done.contains(fmri) {
+            return;
+        }
+
+        // found a path back to a service we are still visiting
+        if let Some(i) = stack.iter().position(|s| *s == fmri) {
+            let mut cycle: Vec<_> =
+                stack[i..].iter().map(|s| s.to_string()).collect();
+
+            // start every cycle at the same place so it is only found once
+            let min = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap();
+            cycle.rotate_left(min);
+            if !cycles.contains(&cycle) {
+                cycles.push(cycle);
+            }
+            return;
+        }
+
+        let svc = match self.get(fmri) {
+            Some(svc) => svc,
+            None => return,
+        };
+
+        stack.push(&svc.fmri);
+        for group in &svc.deps {
+            if group.grouping == Grouping::ExcludeAll {
+                continue;
+            }
+            for target in group.targets.iter().filter(|t| !t.is_file()) {
+                for dep in self.resolve(&target.fmri) {
+                    self.visit(&dep.fmri, stack, done, cycles);
+                }
+            }
+        }
+        stack.pop();
+
+        done.insert(&svc.fmri);
+    }
+}
+
+/// Parse a state as shown by `svcs`.
+pub fn parse_state(s: &str) -> Option<SmfState> {
+    let state = match s {
+        "online" => SmfState::Online,
+        "offline" => SmfState::Offline,
+        "disabled" => SmfState::Disabled,
+        "maintenance" => SmfState::Maintenance,
+        "degraded" => SmfState::Degraded,
+        "legacy_run" => SmfState::Legacy,
+        "uninitialized" => SmfState::Uninitialized,
+        _ => return None,
+    };
+
+    Some(state)
+}
+
+/// Check if a service is running (and satisfies its dependents).
+pub fn is_running(state: &SmfState) -> bool {
+    matches!(state, SmfState::Online | SmfState::Degraded)
+}
+
+/// Get the name of a state as shown by `svcs`.
+pub fn state_name(state: &SmfState) -> &'static str {
+    match state {
+        SmfState::Online => "online",
+        SmfState::Offline => "offline",
+        SmfState::Disabled => "disabled",
+        SmfState::Maintenance => "maintenance",
+        SmfState::Degraded => "degraded",
+        SmfState::Legacy => "legacy_run",
+        SmfState::Uninitialized => "uninitialized",
+    }
+}
+
+/// Print a warning about the graph (without failing).
+fn warn(msg: &str) {
+    eprintln!("{} {}", "warning:".yellow().bold(), msg);
+}
+
+/// Get the service an instance FMRI belongs to (`None` if not an instance).
+fn instance_of(fmri: &str) -> Option<&str> {
+    let rest = fmri.strip_prefix("svc:/")?;
+    let (service, inst) = rest.rsplit_once(':')?;
+    match inst.is_empty() || inst.contains('/') {
+        true => None,
+        false => Some(&fmri[..service.len() + "svc:/".len()]),
+    }
+}
+
+/**
+ * Split the values of a property as printed by `svcprop`.
+ *
+ * Only values without whitespace (states, FMRIs, etc.) are needed so quoted
+ * strings are simply unquoted.
+ */
+fn parse_values(s: &str) -> Vec<String> {
+    s.split_whitespace()
+        .map(|value| value.trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Parse a `time` property like `1696852800.123456789` (UTC).
+fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    let (secs, frac) = s.split_once('.').unwrap_or((s, "0"));
+    let secs = secs.parse().ok()?;
+
+    // the fraction may have any number of digits (up to nanoseconds)
+    let frac: String =
+        frac.chars().chain("000000000".chars()).take(9).collect();
+    let nanos = frac.parse().ok()?;
+
+    DateTime::from_timestamp(secs, nanos).map(|d| d.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVCPROP_OUTPUT: &str = "\
+svc:/network/ssh:default/:properties/general/enabled boolean true
+svc:/network/ssh:default/:properties/general/entity_stability astring Unstable
+svc:/network/ssh:default/:properties/fs-local/grouping astring require_all
+svc:/network/ssh:default/:properties/fs-local/restart_on astring none
+svc:/network/ssh:default/:properties/fs-local/type astring service
+svc:/network/ssh:default/:properties/fs-local/entities fmri svc:/system/filesystem/local
+svc:/network/ssh:default/:properties/fs-autofs/grouping astring optional_all
+svc:/network/ssh:default/:properties/fs-autofs/restart_on astring none
+svc:/network/ssh:default/:properties/fs-autofs/entities fmri svc:/system/filesystem/autofs
+svc:/network/ssh:default/:properties/config_data/grouping astring require_all
+svc:/network/ssh:default/:properties/config_data/restart_on astring restart
+svc:/network/ssh:default/:properties/config_data/entities fmri file://localhost/etc/ssh/sshd_config
+svc:/network/ssh:default/:properties/network/grouping astring require_any
+svc:/network/ssh:default/:properties/network/restart_on astring error
+svc:/network/ssh:default/:properties/network/entities fmri svc:/network/loopback svc:/network/physical
+svc:/network/ssh:default/:properties/tm_common_name/C ustring \"SSH server\"
+svc:/network/ssh:default/:properties/restarter/state astring online
+svc:/network/ssh:default/:properties/restarter/state_timestamp time 1696852800.123456000
+svc:/network/ssh/:properties/general/entity_stability astring Unstable
+svc:/system/filesystem/local:default/:properties/general/enabled boolean true
+svc:/system/filesystem/local:default/:properties/restarter/state astring online
+svc:/network/loopback:default/:properties/general/enabled boolean true
+svc:/network/loopback:default/:properties/restarter/state astring online
+svc:/network/physical:default/:properties/general/enabled boolean true
+svc:/network/physical:default/:properties/restarter/state astring online
+svc:/network/physical:nwam/:properties/general/enabled boolean true
+svc:/network/physical:nwam/:properties/general_ovr/enabled boolean false
+svc:/network/physical:nwam/:properties/restarter/state astring disabled
+svc:/site/app:default/:properties/general/enabled boolean true
+svc:/site/app:default/:properties/ssh/grouping astring require_all
+svc:/site/app:default/:properties/ssh/restart_on astring refresh
+svc:/site/app:default/:properties/ssh/entities fmri svc:/network/ssh:default
+svc:/site/app:default/:properties/restarter/state astring maintenance
+";
+
+    /// Format the properties of a dependency like `svcprop -f` does.
+    fn dependency(fmri: &str, pg: &str, label: &str, entities: &str) -> String {
+        let (grouping, restart_on) = label.split_once('/').unwrap();
+        let prefix = format!("{}/:properties/{}", fmri, pg);
+
+        format!(
+            "{p}/grouping astring {}\n\
+             {p}/restart_on astring {}\n\
+             {p}/entities fmri {}\n",
+            grouping,
+            restart_on,
+            entities,
+            p = prefix
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        assert_eq!(graph.services().count(), 6);
+
+        let ssh = graph.get("svc:/network/ssh:default").unwrap();
+        assert_eq!(ssh.state, SmfState::Online);
+        assert_eq!(ssh.deps.len(), 4);
+
+        let group = &ssh.deps[3];
+        assert_eq!(group.grouping, Grouping::RequireAny);
+        assert_eq!(group.restart_on, RestartOn::Error);
+        assert_eq!(group.label(), "require_any/error");
+        assert_eq!(
+            group.targets,
+            [
+                DepTarget {
+                    fmri: "svc:/network/loopback".into(),
+                    state: "online".into()
+                },
+                DepTarget {
+                    fmri: "svc:/network/physical".into(),
+                    state: "online".into()
+                },
+            ]
+        );
+
+        assert!(ssh.deps[2].targets[0].is_file());
+        assert_eq!(ssh.deps[1].targets[0].state, "absent");
+
+        let time = ssh.state_time.unwrap();
+        assert_eq!(time.and_utc().timestamp(), 1696852800);
+        assert_eq!(time.and_utc().timestamp_subsec_micros(), 123456);
+        assert_eq!(
+            parse_timestamp("1696852800").unwrap(),
+            time - chrono::Duration::microseconds(123456)
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+
+        let app = graph.get("svc:/site/app:default").unwrap();
+        assert_eq!(app.state, SmfState::Maintenance);
+
+        // invalid properties are skipped (and recorded)
+        let graph = Graph::parse(
+            "\
+state online
+svc:/a:b/:properties/restarter/state astring bogus
+svc:/a:b/:properties/ok/grouping astring require_all
+svc:/a:b/:properties/ok/restart_on astring none
+svc:/a:b/:properties/ok/entities fmri svc:/c
+svc:/a:b/:properties/bad/grouping astring require_some
+svc:/a:b/:properties/bad/restart_on astring none
+svc:/a:b/:properties/bad/entities fmri svc:/d
+",
+        )
+        .unwrap();
+        assert_eq!(graph.invalid, ["state online"]);
+
+        let svc = graph.get("svc:/a:b").unwrap();
+        assert_eq!(svc.state, SmfState::Uninitialized);
+        assert_eq!(svc.deps.len(), 1);
+        assert_eq!(svc.deps[0].targets[0].fmri, "svc:/c");
+        assert_eq!(
+            svc.invalid,
+            ["state: bogus", "dependency bad: require_some/none"]
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+
+        let fmris = |fmri| -> Vec<_> {
+            graph.resolve(fmri).iter().map(|svc| svc.fmri.clone()).collect()
+        };
+
+        assert_eq!(
+            fmris("svc:/network/physical"),
+            ["svc:/network/physical:default", "svc:/network/physical:nwam"]
+        );
+        assert_eq!(fmris("svc:/network/loopback:default").len(), 1);
+        assert!(fmris("svc:/network").is_empty());
+        assert!(fmris("svc:/network/ssh").len() == 1);
+        assert!(fmris("svc:/system/filesystem/autofs").is_empty());
+    }
+
+    #[test]
+    fn test_dependents() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+
+        let found: Vec<_> = graph
+            .dependents("svc:/network/physical:nwam")
+            .iter()
+            .map(|(svc, group)| format!("{} {}", svc.fmri, group.label()))
+            .collect();
+        assert_eq!(found, ["svc:/network/ssh:default require_any/error"]);
+
+        assert_eq!(graph.dependents("svc:/site/app:default").len(), 0);
+    }
+
+    #[test]
+    fn test_members() {
+        let s = [
+            SVCPROP_OUTPUT.to_string(),
+            dependency(
+                "svc:/milestone/test:default",
+                "app",
+                "require_all/none",
+                "svc:/site/app",
+            ),
+            dependency(
+                "svc:/milestone/test:default",
+                "any",
+                "require_any/none",
+                "svc:/network/physical:nwam svc:/network/loopback",
+            ),
+            dependency(
+                "svc:/milestone/test:default",
+                "physical",
+                "require_all/none",
+                "svc:/network/physical",
+            ),
+            dependency(
+                "svc:/milestone/test:default",
+                "not",
+                "exclude_all/none",
+                "svc:/system/filesystem/local",
+            ),
+        ]
+        .concat();
+        let graph = Graph::parse(&s).unwrap();
+
+        let members: Vec<_> = graph
+            .members("svc:/milestone/test:default")
+            .into_iter()
+            .map(|(fmri, required)| format!("{} {}", fmri, required))
+            .collect();
+
+        // the disabled `svc:/network/physical:nwam` isn't needed since
+        // `svc:/network/physical:default` is running
+        assert_eq!(
+            members,
+            [
+                "svc:/site/app:default true",
+                "svc:/network/physical:nwam false",
+                "svc:/network/loopback:default false",
+                "svc:/network/physical:default true",
+                "svc:/network/ssh:default true",
+                "svc:/system/filesystem/local:default true",
+            ]
+        );
+
+        assert!(graph.members("svc:/milestone/none:default").is_empty());
+    }
+
+    #[test]
+    fn test_find_cycles() {
+        let graph = Graph::parse(SVCPROP_OUTPUT).unwrap();
+        assert!(graph.find_cycles().is_empty());
+        assert!(graph.get("svc:/network/ssh:default").unwrap().enabled);
+        assert!(!graph.get("svc:/network/physical:nwam").unwrap().enabled);
+
+        let s = [
+            dependency("svc:/c:default", "a", "require_all/none", "svc:/a"),
+            dependency(
+                "svc:/a:default",
+                "b",
+                "require_all/none",
+                "svc:/b:default",
+            ),
+            dependency("svc:/a:default", "d", "exclude_all/none", "svc:/d"),
+            dependency(
+                "svc:/b:default",
+                "c",
+                "optional_all/none",
+                "svc:/c:default",
+            ),
+            dependency(
+                "svc:/b:default",
+                "b",
+                "require_all/none",
+                "svc:/b:default",
+            ),
+            dependency(
+                "svc:/d:default",
+                "a",
+                "require_all/none",
+                "svc:/a:default",
+            ),
+        ]
+        .concat();
+        let graph = Graph::parse(&s).unwrap();
+
+        assert_eq!(
+            graph.find_cycles(),
+            [
+                vec!["svc:/a:default", "svc:/b:default", "svc:/c:default"],
+                vec!["svc:/b:default"],
+            ]
+        );
+    }
+}
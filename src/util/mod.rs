@@ -2,8 +2,16 @@
 
 use std::time::Duration;
 
+pub mod bunyan;
 pub mod color_aware_string;
+pub mod deps;
+pub mod pager;
+pub mod proc;
+pub mod restarter;
+pub mod rotated;
 pub mod smf;
+pub mod tail;
+pub mod timestamp;
 
 /**
  * Convert a duration to a human-readable string like "5 minutes", "2 hours",
@@ -45,3 +53,84 @@ pub fn relative_duration(t: &Duration) -> String {
 
     String::from("0 seconds")
 }
+
+/**
+ * Convert a number of bytes to a human-readable size like "512B", "4.0K",
+ * "1.5M", etc.
+ *
+ * # Example
+ *
+ * ```
+ * assert_eq!(human_size(1536), "1.5K".to_string());
+ * ```
+ */
+pub fn human_size(bytes: u64) -> String {
+    let units = ["K", "M", "G", "T"];
+
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    for unit in &units[..units.len() - 1] {
+        if size < 1024.0 {
+            return format!("{:.1}{}", size, unit);
+        }
+        size /= 1024.0;
+    }
+
+    format!("{:.1}{}", size, units[units.len() - 1])
+}
+
+/**
+ * Get the Levenshtein distance (number of single character insertions,
+ * deletions or substitutions) between two strings.
+ *
+ * # Example
+ *
+ * ```
+ * assert_eq!(edit_distance("nginx", "ngnix"), 2);
+ * ```
+ */
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+
+    // only the previous row of the matrix is needed
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            let n = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            cur.push(n);
+        }
+        prev = cur;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        let tests = [
+            ("", "", 0),
+            ("", "ssh", 3),
+            ("ssh", "", 3),
+            ("nginx", "nginx", 0),
+            ("nginx", "nginz", 1),
+            ("ssh", "sshd", 1),
+            ("sshd", "ssh", 1),
+            // a transposition is two substitutions
+            ("nginx", "ngnix", 2),
+            ("kitten", "sitting", 3),
+        ];
+
+        for (a, b, want) in tests {
+            assert_eq!(edit_distance(a, b), want, "{} {}", a, b);
+        }
+    }
+}
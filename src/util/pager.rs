@@ -0,0 +1,141 @@
+//! Paging of long output through `$PAGER` (like `git` does).
+//!
+//! While a `Pager` is active stdout is a pipe to the pager, so output shows up
+//! as soon as it is written and commands can keep using `println!`.  The
+//! default pager exits by itself (`less -F`) if the output fits on the screen.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::{Child, Command, Stdio};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use anyhow::{bail, Result};
+
+/// Used when `$PAGER` is not set (`-R` so colors survive).
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// Used for `less` when `$LESS` is not set.
+const DEFAULT_LESS: &str = "FRX";
+
+/// The pid of the running pager (for the signal handler).
+static PAGER_PID: AtomicI32 = AtomicI32::new(0);
+
+pub struct Pager {
+    child: Child,
+    /// The original stdout (the terminal).
+    stdout_fd: RawFd,
+}
+
+impl Pager {
+    /**
+     * Start the pager and redirect stdout to it.
+     *
+     * Returns `None` if stdout isn't a terminal (ie. piped to another
+     * command) or paging is disabled (`PAGER=` or `PAGER=cat`).
+     */
+    pub fn start() -> Result<Option<Pager>> {
+        if !io::stdout().is_terminal() {
+            return Ok(None);
+        }
+
+        let pager = env::var("PAGER").unwrap_or(DEFAULT_PAGER.to_string());
+        if pager.is_empty() || pager == "cat" {
+            return Ok(None);
+        }
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&pager).stdin(Stdio::piped());
+        if env::var_os("LESS").is_none() {
+            cmd.env("LESS", DEFAULT_LESS);
+        }
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("failed to run pager `{}`: {}", pager, err);
+                return Ok(None);
+            }
+        };
+
+        // colors are only used when stdout is a terminal - decide that now
+        // before it is redirected
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+        colored::control::set_override(colorize);
+
+        io::stdout().flush()?;
+
+        // the pipe is only kept open through stdout so the pager sees EOF as
+        // soon as stdout is restored
+        let stdin = child.stdin.take().expect("pager stdin is piped");
+        let stdout_fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+        if stdout_fd < 0 {
+            bail!("failed to dup stdout: {}", io::Error::last_os_error());
+        }
+        if unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(stdout_fd) };
+            bail!("failed to redirect stdout: {}", err);
+        }
+        drop(stdin);
+
+        // make sure the pager gets to finish (and restore the terminal) if
+        // we are interrupted
+        PAGER_PID.store(child.id() as i32, Ordering::SeqCst);
+        let handler = on_signal as extern "C" fn(libc::c_int);
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        }
+
+        Ok(Some(Pager { child, stdout_fd }))
+    }
+
+    /// Restore stdout and wait for the user to quit the pager.
+    pub fn finish(mut self) -> Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if self.stdout_fd < 0 {
+            return Ok(());
+        }
+
+        // the pager may have been quit already
+        let _ = io::stdout().flush();
+
+        let ret = unsafe { libc::dup2(self.stdout_fd, libc::STDOUT_FILENO) };
+        if ret < 0 {
+            bail!("failed to restore stdout: {}", io::Error::last_os_error());
+        }
+        unsafe { libc::close(self.stdout_fd) };
+        self.stdout_fd = -1;
+
+        self.child.wait()?;
+        PAGER_PID.store(0, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+impl Drop for Pager {
+    /// Don't leave the pager behind if the command fails or panics.
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Wait for the pager to exit before dying from `sig`.
+extern "C" fn on_signal(sig: libc::c_int) {
+    let pid = PAGER_PID.load(Ordering::SeqCst);
+
+    unsafe {
+        libc::close(libc::STDOUT_FILENO);
+        if pid > 0 {
+            libc::waitpid(pid, ptr::null_mut(), 0);
+        }
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
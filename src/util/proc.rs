@@ -0,0 +1,360 @@
+//! Process information read from `/proc`.
+//!
+//! On illumos every process has a `/proc/<pid>/psinfo` file containing a
+//! binary `psinfo_t` struct (see `proc(5)`).  The reader here takes the root
+//! directory as an argument so it can be pointed at a fixture directory.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use colored::*;
+
+// offsets into `psinfo_t` (64-bit) - see `<sys/procfs.h>`
+const PSINFO_PID: usize = 8;
+const PSINFO_PPID: usize = 12;
+const PSINFO_UID: usize = 24;
+const PSINFO_SIZE: usize = 48;
+const PSINFO_RSSIZE: usize = 56;
+const PSINFO_PCTCPU: usize = 80;
+const PSINFO_PCTMEM: usize = 82;
+const PSINFO_START: usize = 88;
+const PSINFO_TIME: usize = 104;
+const PSINFO_FNAME: usize = 136;
+const PSINFO_PSARGS: usize = 152;
+const PSINFO_ZONEID: usize = 276;
+const PSINFO_CONTRACT: usize = 280;
+
+/// Minimum number of bytes needed to parse everything we care about.
+pub const PSINFO_MIN_LEN: usize = 288;
+
+const PR_FNAME_LEN: usize = 16;
+const PR_PSARGS_LEN: usize = 80;
+
+/// A single process as seen in `/proc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub uid: u32,
+    /// Virtual size in kilobytes.
+    pub size_kb: u64,
+    /// Resident set size in kilobytes.
+    pub rss_kb: u64,
+    /// % of recent cpu time used (binary fraction, `0x8000` == 100%).
+    pub pctcpu: u16,
+    /// % of system memory used (binary fraction, `0x8000` == 100%).
+    pub pctmem: u16,
+    /// Process start time (seconds since the epoch).
+    pub start_secs: i64,
+    /// User + system cpu time in seconds.
+    pub time_secs: i64,
+    pub zoneid: i32,
+    pub contract: i32,
+    pub fname: String,
+    pub args: String,
+}
+
+impl ProcInfo {
+    /// `pctcpu` converted to a regular percentage.
+    pub fn cpu_percent(&self) -> f64 {
+        binary_fraction_to_percent(self.pctcpu)
+    }
+
+    /// `pctmem` converted to a regular percentage.
+    pub fn mem_percent(&self) -> f64 {
+        binary_fraction_to_percent(self.pctmem)
+    }
+}
+
+/// Reads process information from a `/proc`-like directory.
+pub struct ProcReader {
+    root: PathBuf,
+}
+
+impl ProcReader {
+    pub fn new() -> Self {
+        Self::with_root("/proc")
+    }
+
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Read a single process.
+    pub fn read(&self, pid: u32) -> Result<ProcInfo> {
+        let dir = self.root.join(pid.to_string());
+        let path = dir.join("psinfo");
+        let buf = fs::read(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let mut info = parse_psinfo(&buf)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        // `pr_psargs` is truncated, prefer the full command line if available
+        if let Ok(cmdline) = fs::read(dir.join("cmdline")) {
+            let args: Vec<_> = cmdline
+                .split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(String::from_utf8_lossy)
+                .collect();
+            if !args.is_empty() {
+                info.args = args.join(" ");
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+impl Default for ProcReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tree of processes built from a set of pids (ie. contract members).
+pub struct ProcTree {
+    procs: BTreeMap<u32, ProcInfo>,
+    children: BTreeMap<u32, Vec<u32>>,
+    roots: Vec<u32>,
+}
+
+impl ProcTree {
+    /// Build a tree out of the given processes.  Any process whose parent is
+    /// not in the list becomes a root of the tree.
+    pub fn new(procs: Vec<ProcInfo>) -> Self {
+        let procs: BTreeMap<_, _> =
+            procs.into_iter().map(|p| (p.pid, p)).collect();
+
+        let mut children: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+        let mut roots = vec![];
+        for proc in procs.values() {
+            if proc.pid != proc.ppid && procs.contains_key(&proc.ppid) {
+                children.entry(proc.ppid).or_default().push(proc.pid);
+            } else {
+                roots.push(proc.pid);
+            }
+        }
+
+        Self { procs, children, roots }
+    }
+
+    /// Build a tree from pids by looking each of them up in `/proc`.
+    pub fn from_pids(reader: &ProcReader, pids: &[u32]) -> Self {
+        let procs =
+            pids.iter().filter_map(|pid| reader.read(*pid).ok()).collect();
+        Self::new(procs)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.procs.is_empty()
+    }
+
+    /// Render the tree (indented like `ptree`) with colorized output.
+    pub fn render(&self, users: &HashMap<u32, String>) -> String {
+        let mut lines = vec![];
+        for root in &self.roots {
+            self.render_proc(*root, 0, users, &mut lines);
+        }
+        lines.join("\n")
+    }
+
+    fn render_proc(
+        &self,
+        pid: u32,
+        depth: usize,
+        users: &HashMap<u32, String>,
+        lines: &mut Vec<String>,
+    ) {
+        let proc = &self.procs[&pid];
+        let user = username(users, proc.uid);
+
+        lines.push(format!(
+            "{}{}  {}  {}",
+            "  ".repeat(depth),
+            proc.pid.to_string().cyan(),
+            user.green(),
+            proc.args.black().bold(),
+        ));
+
+        if let Some(children) = self.children.get(&pid) {
+            for child in children {
+                self.render_proc(*child, depth + 1, users, lines);
+            }
+        }
+    }
+}
+
+/// Parse the raw contents of a `psinfo` file.
+pub fn parse_psinfo(buf: &[u8]) -> Result<ProcInfo> {
+    ensure!(
+        buf.len() >= PSINFO_MIN_LEN,
+        "psinfo too short: {} bytes",
+        buf.len()
+    );
+
+    let info = ProcInfo {
+        pid: read_i32(buf, PSINFO_PID) as u32,
+        ppid: read_i32(buf, PSINFO_PPID) as u32,
+        uid: read_i32(buf, PSINFO_UID) as u32,
+        size_kb: read_u64(buf, PSINFO_SIZE),
+        rss_kb: read_u64(buf, PSINFO_RSSIZE),
+        pctcpu: read_u16(buf, PSINFO_PCTCPU),
+        pctmem: read_u16(buf, PSINFO_PCTMEM),
+        start_secs: read_i64(buf, PSINFO_START),
+        time_secs: read_i64(buf, PSINFO_TIME),
+        zoneid: read_i32(buf, PSINFO_ZONEID),
+        contract: read_i32(buf, PSINFO_CONTRACT),
+        fname: read_cstr(&buf[PSINFO_FNAME..PSINFO_FNAME + PR_FNAME_LEN]),
+        args: read_cstr(&buf[PSINFO_PSARGS..PSINFO_PSARGS + PR_PSARGS_LEN]),
+    };
+
+    Ok(info)
+}
+
+/// Load a uid -> username mapping from a `passwd(5)` style file.
+pub fn get_usernames(passwd: &Path) -> Result<HashMap<u32, String>> {
+    let data = fs::read_to_string(passwd)
+        .with_context(|| format!("failed to read {}", passwd.display()))?;
+
+    let users = data
+        .lines()
+        .filter_map(|line| {
+            let mut spl = line.split(':');
+            let name = spl.next()?;
+            let uid = spl.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect();
+
+    Ok(users)
+}
+
+/// Lookup a username for the uid (falling back to the uid itself).
+pub fn username(users: &HashMap<u32, String>, uid: u32) -> String {
+    users.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+}
+
+fn binary_fraction_to_percent(n: u16) -> f64 {
+    n as f64 * 100.0 / 0x8000 as f64
+}
+
+fn read_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_ne_bytes(buf[off..off + 2].try_into().unwrap())
+}
+
+fn read_i32(buf: &[u8], off: usize) -> i32 {
+    i32::from_ne_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], off: usize) -> u64 {
+    u64::from_ne_bytes(buf[off..off + 8].try_into().unwrap())
+}
+
+fn read_i64(buf: &[u8], off: usize) -> i64 {
+    i64::from_ne_bytes(buf[off..off + 8].try_into().unwrap())
+}
+
+fn read_cstr(buf: &[u8]) -> String {
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    fn make_psinfo(pid: i32, ppid: i32, uid: i32, args: &str) -> Vec<u8> {
+        let mut buf = vec![0; 416];
+        buf[PSINFO_PID..PSINFO_PID + 4].copy_from_slice(&pid.to_ne_bytes());
+        buf[PSINFO_PPID..PSINFO_PPID + 4].copy_from_slice(&ppid.to_ne_bytes());
+        buf[PSINFO_UID..PSINFO_UID + 4].copy_from_slice(&uid.to_ne_bytes());
+        buf[PSINFO_RSSIZE..PSINFO_RSSIZE + 8]
+            .copy_from_slice(&2048u64.to_ne_bytes());
+        buf[PSINFO_PCTCPU..PSINFO_PCTCPU + 2]
+            .copy_from_slice(&0x4000u16.to_ne_bytes());
+        buf[PSINFO_CONTRACT..PSINFO_CONTRACT + 4]
+            .copy_from_slice(&42i32.to_ne_bytes());
+
+        let fname = args.split(' ').next().unwrap().as_bytes();
+        buf[PSINFO_FNAME..PSINFO_FNAME + fname.len()].copy_from_slice(fname);
+        buf[PSINFO_PSARGS..PSINFO_PSARGS + args.len()]
+            .copy_from_slice(args.as_bytes());
+
+        buf
+    }
+
+    fn make_fixture() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+
+        let procs = [
+            (100, 1, 0, "/usr/sbin/sshd"),
+            (200, 100, 0, "/usr/sbin/sshd -R"),
+            (300, 200, 101, "-bash"),
+            (400, 1, 101, "/usr/bin/other"),
+        ];
+        for (pid, ppid, uid, args) in procs {
+            let dir = root.path().join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("psinfo"), make_psinfo(pid, ppid, uid, args))
+                .unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_parse_psinfo() {
+        let buf = make_psinfo(123, 1, 0, "/usr/lib/foo -d");
+        let info = parse_psinfo(&buf).unwrap();
+
+        assert_eq!(info.pid, 123);
+        assert_eq!(info.ppid, 1);
+        assert_eq!(info.uid, 0);
+        assert_eq!(info.rss_kb, 2048);
+        assert_eq!(info.contract, 42);
+        assert_eq!(info.fname, "/usr/lib/foo");
+        assert_eq!(info.args, "/usr/lib/foo -d");
+        assert_eq!(info.cpu_percent(), 50.0);
+        assert_eq!(info.mem_percent(), 0.0);
+
+        assert!(parse_psinfo(&buf[..100]).is_err());
+    }
+
+    #[test]
+    fn test_proc_tree() {
+        colored::control::set_override(false);
+
+        let root = make_fixture();
+        let reader = ProcReader::with_root(root.path());
+        let users = HashMap::from([(0, "root".to_string())]);
+
+        let tree = ProcTree::from_pids(&reader, &[100, 200, 300, 999]);
+        assert_eq!(
+            tree.render(&users),
+            [
+                "100  root  /usr/sbin/sshd",
+                "  200  root  /usr/sbin/sshd -R",
+                "    300  101  -bash",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_get_usernames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passwd");
+        fs::write(&path, "root:x:0:0:Super-User:/root:/bin/bash\nbad\n")
+            .unwrap();
+
+        let users = get_usernames(&path).unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(username(&users, 0), "root");
+        assert_eq!(username(&users, 5), "5");
+    }
+}
@@ -0,0 +1,195 @@
+//! Parsing of the lines `svc.startd` writes to service log files.
+//!
+//! The restarter logs every action it takes on a service as a bracketed line
+//! like:
+//!
+//! ```text
+//! [ Oct  9 12:00:00 Executing start method ("/lib/svc/method/sshd start"). ]
+//! [ Oct  9 12:00:01 Method "start" exited with status 0. ]
+//! ```
+
+use std::sync::OnceLock;
+
+use colored::*;
+use regex::Regex;
+
+static LINE_RE: OnceLock<Regex> = OnceLock::new();
+static EXECUTING_RE: OnceLock<Regex> = OnceLock::new();
+static EXITED_RE: OnceLock<Regex> = OnceLock::new();
+static FAILED_RE: OnceLock<Regex> = OnceLock::new();
+
+/// The kind of action the restarter took.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    Enabled,
+    Disabled,
+    /// `Executing <method> method (<command>).`
+    ExecutingMethod {
+        method: String,
+        command: String,
+    },
+    /// `Method "<method>" exited with status <status>.`
+    MethodExited {
+        method: String,
+        status: i32,
+    },
+    /// `Method "<method>" failed due to <reason>.`
+    MethodFailed {
+        method: String,
+        reason: String,
+    },
+    /// `Stopping because <reason>.`
+    Stopping {
+        reason: String,
+    },
+    /// Entering or leaving the maintenance state.
+    Maintenance,
+    /// Anything else the restarter logged.
+    Other,
+}
+
+/// A single line logged by the restarter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestarterEvent {
+    pub kind: EventKind,
+}
+
+impl RestarterEvent {
+    /// Check if this event marks the start of a new instance of the service.
+    pub fn is_start(&self) -> bool {
+        matches!(
+            &self.kind,
+            EventKind::ExecutingMethod { method, .. } if method == "start"
+        )
+    }
+
+    /// Colorize the original line based on the kind of event.
+    pub fn stylize(&self, line: &str) -> String {
+        match &self.kind {
+            EventKind::Enabled | EventKind::Disabled => line.magenta(),
+            EventKind::ExecutingMethod { .. } => line.blue().bold(),
+            EventKind::MethodExited { status: 0, .. } => line.green(),
+            EventKind::MethodExited { .. } => line.red(),
+            EventKind::MethodFailed { .. } => line.red().bold(),
+            EventKind::Stopping { .. } => line.yellow(),
+            EventKind::Maintenance => line.red().bold().reversed(),
+            EventKind::Other => line.blue(),
+        }
+        .to_string()
+    }
+}
+
+/// Parse a restarter line (returns `None` for any other line).
+pub fn parse_event(line: &str) -> Option<RestarterEvent> {
+    let re = LINE_RE.get_or_init(|| {
+        Regex::new(r"^\[ [A-Z][a-z]{2} +\d{1,2} \d{2}:\d{2}:\d{2} (.*) \]$")
+            .unwrap()
+    });
+    let message = re.captures(line.trim_end())?[1].to_string();
+
+    let executing = EXECUTING_RE.get_or_init(|| {
+        Regex::new(r"^Executing (\S+) method \((.*)\)\.?$").unwrap()
+    });
+    let exited = EXITED_RE.get_or_init(|| {
+        Regex::new(r#"^Method "(\S+)" exited with status (-?\d+)\.?$"#).unwrap()
+    });
+    let failed = FAILED_RE.get_or_init(|| {
+        Regex::new(r#"^Method "(\S+)" failed (?:due to )?(.*?)\.?$"#).unwrap()
+    });
+
+    let kind = if message.starts_with("Enabled") {
+        EventKind::Enabled
+    } else if message.starts_with("Disabled") {
+        EventKind::Disabled
+    } else if let Some(caps) = executing.captures(&message) {
+        let command = caps[2].trim_matches('"').to_string();
+        EventKind::ExecutingMethod { method: caps[1].to_string(), command }
+    } else if let Some(caps) = exited.captures(&message) {
+        let status = caps[2].parse().unwrap_or(-1);
+        EventKind::MethodExited { method: caps[1].to_string(), status }
+    } else if let Some(caps) = failed.captures(&message) {
+        let reason = caps[2].to_string();
+        EventKind::MethodFailed { method: caps[1].to_string(), reason }
+    } else if let Some(reason) = message.strip_prefix("Stopping because ") {
+        let reason = reason.trim_end_matches('.').to_string();
+        EventKind::Stopping { reason }
+    } else if message.contains("maintenance") {
+        EventKind::Maintenance
+    } else {
+        EventKind::Other
+    };
+
+    Some(RestarterEvent { kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(line: &str) -> Option<EventKind> {
+        parse_event(line).map(|ev| ev.kind)
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let tests = [
+            ("[ Oct  9 12:00:00 Enabled. ]", EventKind::Enabled),
+            ("[ Oct 9 12:00:00 Disabled. ]", EventKind::Disabled),
+            (
+                r#"[ Oct  9 12:00:00 Executing start method ("/lib/svc/method/sshd start"). ]"#,
+                EventKind::ExecutingMethod {
+                    method: "start".into(),
+                    command: "/lib/svc/method/sshd start".into(),
+                },
+            ),
+            (
+                "[ Oct  9 12:00:00 Executing stop method (:kill). ]",
+                EventKind::ExecutingMethod {
+                    method: "stop".into(),
+                    command: ":kill".into(),
+                },
+            ),
+            (
+                r#"[ Oct  9 12:00:00 Method "start" exited with status 1. ]"#,
+                EventKind::MethodExited { method: "start".into(), status: 1 },
+            ),
+            (
+                r#"[ Oct  9 12:00:00 Method "start" failed due to signal KILL. ]"#,
+                EventKind::MethodFailed {
+                    method: "start".into(),
+                    reason: "signal KILL".into(),
+                },
+            ),
+            (
+                "[ Oct  9 12:00:00 Stopping because all processes in service exited. ]",
+                EventKind::Stopping {
+                    reason: "all processes in service exited".into(),
+                },
+            ),
+            (
+                "[ Oct  9 12:00:00 Restarting too quickly, changing state to maintenance. ]",
+                EventKind::Maintenance,
+            ),
+            ("[ Oct  9 12:00:00 Rereading configuration. ]", EventKind::Other),
+        ];
+
+        for (line, want) in tests {
+            assert_eq!(kind(line), Some(want), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_not_an_event() {
+        let lines = [
+            "",
+            "hello world",
+            "[ not a restarter line ]",
+            "[ Oct  9 12:00:00 missing closing bracket",
+            r#"{"v":0,"level":30,"msg":"[ Oct  9 12:00:00 Enabled. ]"}"#,
+        ];
+
+        for line in lines {
+            assert_eq!(kind(line), None, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Discovery of log files rotated by `logadm`.
+//!
+//! `logadm` renames `foo.log` to `foo.log.0` (shifting any existing
+//! `foo.log.0` to `foo.log.1` and so on), so the highest number is the oldest.
+//! Rotated files may also be compressed (`foo.log.0.gz`).
+
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+
+use super::tail::read_lines_from;
+
+/// Find the rotated siblings of a log file, ordered oldest first.
+pub fn find_rotated(path: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Ok(vec![]),
+    };
+    let prefix = format!("{}.", name);
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("failed to read dir {}", dir.display()))?;
+
+    let mut found = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        let n = file_name
+            .strip_prefix(&prefix)
+            .map(|suffix| suffix.strip_suffix(".gz").unwrap_or(suffix))
+            .and_then(|suffix| suffix.parse::<u32>().ok());
+        if let Some(n) = n {
+            found.push((n, entry.path()));
+        }
+    }
+
+    found.sort_by_key(|(n, _)| Reverse(*n));
+
+    Ok(found.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Check if a log file is gzip compressed (based on its name).
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Read all lines of a (possibly compressed) log file.
+pub fn read_log_lines(path: &Path) -> Result<Vec<String>> {
+    if !is_compressed(path) {
+        return read_lines_from(path, 0);
+    }
+
+    let file = File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    let mut data = vec![];
+    GzDecoder::new(file)
+        .read_to_end(&mut data)
+        .with_context(|| format!("failed to decompress {}", path.display()))?;
+
+    let data = String::from_utf8_lossy(&data);
+    Ok(data.lines().map(|line| line.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_find_rotated() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let names = [
+            "svc.log",
+            "svc.log.0",
+            "svc.log.1",
+            "svc.log.10",
+            "svc.log.2.gz",
+            "svc.log.gz",
+            "svc.log.old",
+            "svc.log.0.bak",
+            "other.log.0",
+        ];
+        for name in names {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let found: Vec<_> = find_rotated(&dir.join("svc.log"))
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            found,
+            ["svc.log.10", "svc.log.2.gz", "svc.log.1", "svc.log.0"]
+        );
+
+        assert!(find_rotated(&dir.join("none.log")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_log_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let path = dir.join("svc.log.0");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        assert_eq!(read_log_lines(&path).unwrap(), ["one", "two"]);
+
+        let path = dir.join("svc.log.0.gz");
+        let mut encoder = GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        );
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        encoder.finish().unwrap();
+
+        assert!(is_compressed(&path));
+        assert_eq!(read_log_lines(&path).unwrap(), ["one", "two"]);
+
+        fs::write(&path, "not gzip").unwrap();
+        assert!(read_log_lines(&path).is_err());
+    }
+}
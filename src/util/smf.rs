@@ -1,24 +1,24 @@
-use std::process::Command;
+use std::cmp;
+use std::slice;
 
-use anyhow::{bail, ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime};
 use colored::*;
+use libcontract::status::{ContractStatus, Detail};
 use regex::Regex;
-use smf::SmfState;
+use smf::{Query, QuerySelection, SmfState, SvcStatus};
 
-pub fn get_ptree_for_fmri(fmri: &str) -> Result<String> {
-    let output = Command::new("ptree")
-        .args(["-gs", fmri])
-        .output()
-        .with_context(|| format!("failed to get ptree for fmri: {}", fmri))?;
-
-    if !output.status.success() {
-        bail!("failed to run ptree for fmri {}: {:#?}", fmri, output.status);
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+/// Get the pids of all processes in the given contract.
+pub fn get_contract_members(ctid: usize) -> Result<Vec<u32>> {
+    let status =
+        ContractStatus::new(ctid as u32, Detail::All).map_err(|e| {
+            anyhow!("failed to get contract status {}: {:?}", ctid, e)
+        })?;
+    let members = status.get_members().map_err(|e| {
+        anyhow!("failed to get contract members {}: {:?}", ctid, e)
+    })?;
 
-    Ok(stdout)
+    Ok(members.iter().map(|pid| *pid as u32).collect())
 }
 
 /// Get a suitable char for the state (as a `String`).
@@ -102,6 +102,173 @@ pub fn stylize_smf_fmri(fmri: &str) -> Result<String> {
     Ok(out)
 }
 
+/**
+ * Get a short name for an FMRI suitable for prefixing output.
+ *
+ * The last component of the service name is used, along with the instance
+ * name if it isn't `default`.  For example:
+ *
+ * svc:/network/ssh:default -> ssh
+ * svc:/site/app:worker1 -> app:worker1
+ */
+pub fn fmri_short_name(fmri: &str) -> String {
+    let fmri = fmri.strip_prefix("svc:/").unwrap_or(fmri);
+    let (name, inst) = match fmri.rsplit_once(':') {
+        Some((name, inst)) => (name, Some(inst)),
+        None => (fmri, None),
+    };
+    let name = name.rsplit('/').next().unwrap_or(name);
+
+    match inst {
+        None | Some("default") => name.to_string(),
+        Some(inst) => format!("{}:{}", name, inst),
+    }
+}
+
+/**
+ * Get the status of every service matching the patterns given, along with
+ * the patterns that didn't match any services.
+ *
+ * Unmatched patterns are reported (along with similarly named services)
+ * instead of being silently ignored.
+ */
+pub fn get_status_matching(
+    q: &Query,
+    patterns: &[String],
+) -> Result<(Vec<SvcStatus>, Vec<String>)> {
+    let (svcs, unmatched) = find_status_matching(q, patterns)?;
+    if unmatched.is_empty() {
+        return Ok((svcs, unmatched));
+    }
+
+    let fmris: Vec<_> = match q.get_status_all() {
+        Ok(all) => all.map(|svc| svc.fmri).collect(),
+        Err(_) => vec![],
+    };
+    for pattern in &unmatched {
+        eprintln!(
+            "{} no services match: {}",
+            "warning:".yellow().bold(),
+            pattern.bold()
+        );
+
+        let suggestions = suggest_fmris(pattern, &fmris);
+        if !suggestions.is_empty() {
+            eprintln!("  did you mean: {}", suggestions.join(", "));
+        }
+    }
+
+    Ok((svcs, unmatched))
+}
+
+/// Like `get_status_matching` but without reporting unmatched patterns.
+pub fn find_status_matching(
+    q: &Query,
+    patterns: &[String],
+) -> Result<(Vec<SvcStatus>, Vec<String>)> {
+    let svcs: Vec<_> = q
+        .get_status(QuerySelection::ByPattern(patterns))
+        .with_context(|| format!("failed to get_status: {:?}", patterns))?
+        .collect();
+
+    // with multiple patterns each one that doesn't obviously match one of
+    // the services found has to be checked on its own
+    let unmatched = match patterns {
+        [_] if !svcs.is_empty() => vec![],
+        [_] => patterns.to_vec(),
+        _ => patterns
+            .iter()
+            .filter(|pattern| {
+                !svcs.iter().any(|svc| fmri_matches(pattern, &svc.fmri))
+            })
+            .filter(|pattern| {
+                let select =
+                    QuerySelection::ByPattern(slice::from_ref(*pattern));
+                q.get_status(select).is_ok_and(|mut svcs| svcs.next().is_none())
+            })
+            .cloned()
+            .collect(),
+    };
+
+    Ok((svcs, unmatched))
+}
+
+/**
+ * Check if a pattern matches an FMRI the way `svcs` matches it: the full
+ * FMRI (with or without `svc:/`) or its trailing components, with or
+ * without the instance (ie. `ssh`, `network/ssh` or `ssh:default`).
+ *
+ * Glob patterns never match here so they are left to smf.
+ */
+pub fn fmri_matches(pattern: &str, fmri: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        return false;
+    }
+
+    let name = match fmri.strip_prefix("svc:/") {
+        Some(name) => name,
+        None => return pattern == fmri,
+    };
+    let service = name.rsplit_once(':').map_or(name, |(service, _)| service);
+
+    match pattern.strip_prefix("svc:/") {
+        Some(pattern) => pattern == name || pattern == service,
+        None => [name, service].iter().any(|form| {
+            *form == pattern
+                || form
+                    .strip_suffix(pattern)
+                    .is_some_and(|rest| rest.ends_with('/'))
+        }),
+    }
+}
+
+/**
+ * Find the FMRIs closest to a pattern that didn't match anything (best
+ * first).
+ *
+ * The pattern is compared against the full FMRI, the service name and its
+ * short name, so `nginz` suggests `svc:/network/nginx:default`.
+ */
+pub fn suggest_fmris<'a>(pattern: &str, fmris: &'a [String]) -> Vec<&'a str> {
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let pattern = pattern.strip_prefix("svc:/").unwrap_or(pattern);
+    let pattern = pattern.replace(['*', '?'], "").to_lowercase();
+    let len = pattern.chars().count();
+    if len == 0 {
+        return vec![];
+    }
+    let max = cmp::max(2, len / 3);
+
+    let mut found: Vec<_> = fmris
+        .iter()
+        .filter_map(|fmri| {
+            let full = fmri.strip_prefix("svc:/").unwrap_or(fmri);
+            let name = full.rsplit_once(':').map_or(full, |(name, _)| name);
+            let short = fmri_short_name(fmri);
+
+            let dist = [full, name, &short]
+                .iter()
+                .map(|form| {
+                    let form = form.to_lowercase();
+                    // typos of names contained in the pattern count as close
+                    let contained = form.chars().count() >= 3
+                        && (form.contains(&pattern) || pattern.contains(&form));
+                    match contained {
+                        true => 0,
+                        false => super::edit_distance(&pattern, &form),
+                    }
+                })
+                .min()?;
+
+            (dist <= max).then_some((dist, fmri.as_str()))
+        })
+        .collect();
+    found.sort();
+
+    found.into_iter().take(MAX_SUGGESTIONS).map(|(_, fmri)| fmri).collect()
+}
+
 /**
  * Parse a date as seen by `svcs`.
  *
@@ -192,6 +359,70 @@ pub fn parse_smf_date(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fmri_short_name() {
+        let fmris = [
+            ("svc:/network/ssh:default", "ssh"),
+            ("svc:/site/app:worker1", "app:worker1"),
+            ("svc:/milestone/multi-user", "multi-user"),
+            ("network/ssh", "ssh"),
+        ];
+
+        for (fmri, want) in fmris {
+            assert_eq!(fmri_short_name(fmri), want);
+        }
+    }
+
+    #[test]
+    fn test_fmri_matches() {
+        let tests = [
+            ("svc:/network/ssh:default", true),
+            ("svc:/network/ssh", true),
+            ("network/ssh:default", true),
+            ("network/ssh", true),
+            ("ssh:default", true),
+            ("ssh", true),
+            ("sh", false),
+            ("work/ssh", false),
+            ("default", false),
+            ("ssh:def", false),
+            ("svc:/ssh", false),
+            ("svc:/network/ssh:other", false),
+            ("ss*", false),
+        ];
+
+        for (pattern, want) in tests {
+            let got = fmri_matches(pattern, "svc:/network/ssh:default");
+            assert_eq!(got, want, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_suggest_fmris() {
+        let fmris: Vec<String> = [
+            "svc:/network/ssh:default",
+            "svc:/network/nginx:default",
+            "svc:/site/app:worker1",
+            "svc:/milestone/multi-user:default",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let tests: [(&str, &[&str]); 6] = [
+            ("nginz", &["svc:/network/nginx:default"]),
+            ("nginx-typo", &["svc:/network/nginx:default"]),
+            ("network/shh", &["svc:/network/ssh:default"]),
+            ("site/ap*", &["svc:/site/app:worker1"]),
+            ("multiuser", &["svc:/milestone/multi-user:default"]),
+            ("postgresql", &[]),
+        ];
+
+        for (pattern, want) in tests {
+            assert_eq!(suggest_fmris(pattern, &fmris), want, "{}", pattern);
+        }
+    }
+
     #[test]
     fn test_under_a_day() {
         let now = NaiveDate::from_ymd_opt(2023, 10, 9)
@@ -0,0 +1,491 @@
+//! Native `tail` implementation for reading (and following) log files.
+//!
+//! SMF log files are rotated by `logadm`, which either renames the file and
+//! creates a new one or copies and truncates it in place.  `Follower` handles
+//! both cases the same way `tail -F` does.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// How many bytes to read at a time when searching backwards in a file.
+const CHUNK_SIZE: u64 = 8 * 1024;
+
+/// Read all lines of a file starting at the given byte offset.
+pub fn read_lines_from(path: &Path, offset: u64) -> Result<Vec<String>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut lines = vec![];
+    for line in BufReader::new(file).split(b'\n') {
+        let line = line?;
+        lines.push(String::from_utf8_lossy(&line).to_string());
+    }
+
+    Ok(lines)
+}
+
+/**
+ * Find the byte offset where the last `n` lines of the file start.
+ *
+ * The file is read backwards in chunks so this is cheap even for very large
+ * log files.  A trailing newline at the end of the file does not count as the
+ * start of an (empty) line.
+ */
+pub fn find_last_lines_offset<R: Read + Seek>(
+    reader: &mut R,
+    n: usize,
+) -> Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if n == 0 {
+        return Ok(len);
+    }
+
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    let mut pos = len;
+    let mut found = 0;
+
+    while pos > 0 {
+        let size = CHUNK_SIZE.min(pos);
+        pos -= size;
+
+        reader.seek(SeekFrom::Start(pos))?;
+        let chunk = &mut buf[..size as usize];
+        reader.read_exact(chunk)?;
+
+        for (i, b) in chunk.iter().enumerate().rev() {
+            let off = pos + i as u64;
+            if *b != b'\n' || off == len - 1 {
+                continue;
+            }
+
+            found += 1;
+            if found == n {
+                return Ok(off + 1);
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+/**
+ * Find the byte offset of the last line in the file for which `pred` returns
+ * true.
+ *
+ * Like `find_last_lines_offset` the file is read backwards in chunks, so
+ * only as much of the file as needed is read.
+ */
+pub fn find_last_line_matching<R, F>(
+    reader: &mut R,
+    pred: F,
+) -> Result<Option<u64>>
+where
+    R: Read + Seek,
+    F: Fn(&str) -> bool,
+{
+    let len = reader.seek(SeekFrom::End(0))?;
+
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    let mut pos = len;
+
+    // the end of a line that continues past the current chunk
+    let mut rest: Vec<u8> = vec![];
+
+    while pos > 0 {
+        let size = CHUNK_SIZE.min(pos);
+        pos -= size;
+
+        reader.seek(SeekFrom::Start(pos))?;
+        let chunk = &mut buf[..size as usize];
+        reader.read_exact(chunk)?;
+
+        let mut line_end = chunk.len();
+        for i in (0..chunk.len()).rev() {
+            if chunk[i] != b'\n' {
+                continue;
+            }
+
+            let mut line = chunk[i + 1..line_end].to_vec();
+            line.append(&mut rest);
+            if pred(&String::from_utf8_lossy(&line)) {
+                return Ok(Some(pos + i as u64 + 1));
+            }
+
+            line_end = i;
+        }
+
+        let mut line = chunk[..line_end].to_vec();
+        line.append(&mut rest);
+        rest = line;
+    }
+
+    // the very first line of the file
+    if len > 0 && pred(&String::from_utf8_lossy(&rest)) {
+        return Ok(Some(0));
+    }
+
+    Ok(None)
+}
+
+/**
+ * Binary search a file for the first line that is not "before" some target
+ * and return its byte offset.
+ *
+ * `is_before` is called with lines of the file and returns whether the line
+ * comes before the target, or `None` if it can't tell (ie. the line has no
+ * timestamp) in which case the following lines are tried.  This assumes the
+ * lines are (mostly) in order, which is true for log files.
+ *
+ * The offset returned may be a little early - callers should still check
+ * each line they read from it.
+ */
+pub fn bisect_lines<R, F>(reader: &mut R, is_before: F) -> Result<u64>
+where
+    R: Read + Seek,
+    F: Fn(&str) -> Option<bool>,
+{
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut reader = BufReader::new(reader);
+
+    let mut lo = 0;
+    let mut hi = len;
+    let mut line = vec![];
+
+    // once the range is small enough, a linear scan is cheaper
+    while hi - lo > CHUNK_SIZE {
+        let mid = lo + (hi - lo) / 2;
+        reader.seek(SeekFrom::Start(mid))?;
+
+        // skip the (likely partial) line we landed in
+        line.clear();
+        let mut pos = mid + reader.read_until(b'\n', &mut line)? as u64;
+
+        // find the next line we can compare (that ends before `hi`, so `lo`
+        // never passes it)
+        let mut found = None;
+        while pos < hi {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line)?;
+            if n == 0 || pos + n as u64 > hi {
+                break;
+            }
+            pos += n as u64;
+
+            if let Some(before) = is_before(&String::from_utf8_lossy(&line)) {
+                found = Some(before);
+                break;
+            }
+        }
+
+        match found {
+            Some(true) => lo = pos,
+            Some(false) | None => hi = mid,
+        }
+    }
+
+    Ok(lo)
+}
+
+/// Follow a file as it grows, surviving rotation and truncation.
+pub struct Follower {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    ino: u64,
+    dev: u64,
+    pos: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Start following the file at the given byte offset.
+    pub fn new(path: &Path, pos: u64) -> Self {
+        let mut follower = Self {
+            path: path.to_path_buf(),
+            reader: None,
+            ino: 0,
+            dev: 0,
+            pos,
+            partial: vec![],
+        };
+
+        // the file may not exist yet - it will be picked up when it does
+        let _ = follower.open();
+
+        follower
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return any complete lines written since the last poll.
+    pub fn poll(&mut self) -> Result<Vec<String>> {
+        let mut lines = vec![];
+
+        if self.reader.is_none() && self.open().is_err() {
+            return Ok(lines);
+        }
+
+        // read whatever is left in the currently opened file
+        self.read_lines(&mut lines)?;
+
+        match fs::metadata(&self.path) {
+            Ok(md) if md.ino() != self.ino || md.dev() != self.dev => {
+                // file was rotated - the old file is drained so start over
+                // on the new one
+                self.flush_partial(&mut lines);
+                self.pos = 0;
+                if self.open().is_ok() {
+                    self.read_lines(&mut lines)?;
+                }
+            }
+            Ok(md) if md.len() < self.pos => {
+                // file was truncated in place
+                self.flush_partial(&mut lines);
+                self.pos = 0;
+                if let Some(reader) = self.reader.as_mut() {
+                    reader.seek(SeekFrom::Start(0))?;
+                }
+                self.read_lines(&mut lines)?;
+            }
+            Ok(_) => (),
+            Err(_) => {
+                // file was removed - wait for it to come back
+                self.flush_partial(&mut lines);
+                self.reader = None;
+                self.pos = 0;
+            }
+        }
+
+        Ok(lines)
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let mut file = File::open(&self.path)?;
+        let md = file.metadata()?;
+
+        // a new file may be smaller than where we left off
+        if md.len() < self.pos {
+            self.pos = 0;
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+
+        self.ino = md.ino();
+        self.dev = md.dev();
+        self.reader = Some(BufReader::new(file));
+
+        Ok(())
+    }
+
+    fn read_lines(&mut self, lines: &mut Vec<String>) -> Result<()> {
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => return Ok(()),
+        };
+
+        loop {
+            let n = reader.read_until(b'\n', &mut self.partial)?;
+            if n == 0 {
+                break;
+            }
+            self.pos += n as u64;
+
+            if self.partial.ends_with(b"\n") {
+                self.partial.pop();
+                lines.push(String::from_utf8_lossy(&self.partial).to_string());
+                self.partial.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush_partial(&mut self, lines: &mut Vec<String>) {
+        if !self.partial.is_empty() {
+            lines.push(String::from_utf8_lossy(&self.partial).to_string());
+            self.partial.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+    use std::io::{Cursor, Write};
+
+    fn append(path: &Path, data: &str) {
+        let mut f =
+            OpenOptions::new().create(true).append(true).open(path).unwrap();
+        f.write_all(data.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_last_lines_offset() {
+        let data = "one\ntwo\nthree\n";
+        let tests = [(0, 14), (1, 8), (2, 4), (3, 0), (10, 0)];
+
+        for (n, want) in tests {
+            let mut cursor = Cursor::new(data);
+            let off = find_last_lines_offset(&mut cursor, n).unwrap();
+            assert_eq!(off, want, "n = {}", n);
+        }
+
+        // no trailing newline
+        let mut cursor = Cursor::new("one\ntwo");
+        assert_eq!(find_last_lines_offset(&mut cursor, 1).unwrap(), 4);
+
+        // empty
+        let mut cursor = Cursor::new("");
+        assert_eq!(find_last_lines_offset(&mut cursor, 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_last_line_matching() {
+        let data = "start\na\nstart\nb\nc\n";
+        let tests =
+            [("start", Some(8)), ("a", Some(6)), ("c", Some(16)), ("d", None)];
+
+        for (want, off) in tests {
+            let mut cursor = Cursor::new(data);
+            let found = find_last_line_matching(&mut cursor, |l| l == want);
+            assert_eq!(found.unwrap(), off, "{}", want);
+        }
+
+        // first line (without trailing newline)
+        let mut cursor = Cursor::new("start");
+        let found = find_last_line_matching(&mut cursor, |l| l == "start");
+        assert_eq!(found.unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_last_line_matching_large() {
+        // lines crossing chunk boundaries
+        let mut data: Vec<_> =
+            (0..5000).map(|i| format!("line {}", i)).collect();
+        data[1234] = "marker".to_string();
+        let data = data.join("\n") + "\n";
+
+        let mut cursor = Cursor::new(&data);
+        let off = find_last_line_matching(&mut cursor, |l| l == "marker")
+            .unwrap()
+            .unwrap();
+        assert!(data[off as usize..].starts_with("marker\nline 1235\n"));
+    }
+
+    #[test]
+    fn test_bisect_lines() {
+        // every 10th line has no "timestamp"
+        let data: Vec<_> = (0..20000)
+            .map(|i| match i % 10 {
+                0 => "  continued".to_string(),
+                _ => format!("{:05} line", i),
+            })
+            .collect();
+        let data = data.join("\n") + "\n";
+
+        let is_before = |target: u32| {
+            move |line: &str| {
+                let n: u32 = line.get(..5)?.parse().ok()?;
+                Some(n < target)
+            }
+        };
+
+        for target in [0, 1, 5555, 12345, 19999] {
+            let mut cursor = Cursor::new(&data);
+            let off = bisect_lines(&mut cursor, is_before(target)).unwrap();
+
+            // the offset is at the start of a line and not past the target
+            let rest = &data[off as usize..];
+            assert!(off == 0 || data.as_bytes()[off as usize - 1] == b'\n');
+            let want = format!("{:05} line", target);
+            let idx = rest.find(&want).unwrap_or(0);
+            assert!(idx < CHUNK_SIZE as usize * 2, "target {}", target);
+        }
+
+        // past the end
+        let mut cursor = Cursor::new(&data);
+        let off = bisect_lines(&mut cursor, is_before(99999)).unwrap();
+        assert!(data.len() as u64 - off <= CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_bisect_lines_straddling() {
+        // a long line that is "before" the target, surrounded by long runs of
+        // lines without timestamps, ends up straddling the upper bound
+        let run = "  continued\n".repeat(2000);
+        let long = format!("00001 {}\n", "x".repeat(30000));
+        let data = format!("{}{}{}{}", run, long, run, run);
+
+        let mut cursor = Cursor::new(&data);
+        let off = bisect_lines(&mut cursor, |line| {
+            let n: u32 = line.get(..5)?.parse().ok()?;
+            Some(n < 99999)
+        })
+        .unwrap();
+
+        assert!(off == 0 || data.as_bytes()[off as usize - 1] == b'\n');
+        assert!(off <= (run.len() + long.len()) as u64);
+    }
+
+    #[test]
+    fn test_last_lines_large() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.log");
+        let data: Vec<_> = (0..10000).map(|i| format!("line {}", i)).collect();
+        fs::write(&path, data.join("\n") + "\n").unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let offset = find_last_lines_offset(&mut file, 3).unwrap();
+        let lines = read_lines_from(&path, offset).unwrap();
+        assert_eq!(lines, ["line 9997", "line 9998", "line 9999"]);
+    }
+
+    #[test]
+    fn test_follow_append_and_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("truncate.log");
+        fs::write(&path, "old\n").unwrap();
+
+        let mut follower = Follower::new(&path, 4);
+        assert!(follower.poll().unwrap().is_empty());
+
+        // partial lines are held until complete
+        append(&path, "one\ntw");
+        assert_eq!(follower.poll().unwrap(), ["one"]);
+        append(&path, "o\n");
+        assert_eq!(follower.poll().unwrap(), ["two"]);
+
+        // copy-truncate
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(follower.poll().unwrap(), ["new"]);
+    }
+
+    #[test]
+    fn test_follow_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rotate.log");
+        let rotated = dir.path().join("rotate.log.0");
+        fs::write(&path, "").unwrap();
+
+        let mut follower = Follower::new(&path, 0);
+
+        // lines written just before rotation are not lost
+        append(&path, "before\n");
+        fs::rename(&path, &rotated).unwrap();
+        assert_eq!(follower.poll().unwrap(), ["before"]);
+
+        // missing file is waited on
+        assert!(follower.poll().unwrap().is_empty());
+
+        append(&path, "after\n");
+        assert_eq!(follower.poll().unwrap(), ["after"]);
+    }
+}
@@ -0,0 +1,366 @@
+//! Timestamp parsing for log lines.
+//!
+//! All timestamps are normalized to a UTC `NaiveDateTime` so lines from
+//! different log files (and formats) can be compared with each other.
+
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime,
+    NaiveTime, TimeZone,
+};
+use regex::Regex;
+
+use super::bunyan;
+
+static ISO8601_RE: OnceLock<Regex> = OnceLock::new();
+static DURATION_RE: OnceLock<Regex> = OnceLock::new();
+
+/**
+ * Parse the timestamp a log line starts with (if any).
+ *
+ * Supported formats:
+ *
+ * - SMF restarter lines: `[ Oct  9 12:00:00 Executing start method ... ]`
+ *   (local time, year is inferred from `now`)
+ * - ISO8601 prefixes: `2023-10-09T12:00:00.123Z`, `2023-10-09 12:00:00`
+ *   (local time unless an offset is given)
+ * - bunyan (JSON) records: the `time` field is parsed as ISO8601
+ */
+pub fn parse_line_timestamp(
+    now: &NaiveDateTime,
+    line: &str,
+) -> Option<NaiveDateTime> {
+    if let Some(rest) = line.strip_prefix("[ ") {
+        return parse_restarter_timestamp(now, rest);
+    }
+
+    if let Some(obj) = bunyan::parse_json(line) {
+        return bunyan::record_time(&obj).and_then(parse_iso8601_prefix);
+    }
+
+    parse_iso8601_prefix(line)
+}
+
+/// Parse a `Mon DD HH:MM:SS` timestamp as found in SMF restarter lines.
+fn parse_restarter_timestamp(
+    now: &NaiveDateTime,
+    s: &str,
+) -> Option<NaiveDateTime> {
+    let mut spl = s.split_whitespace();
+    let date = format!(
+        "{} {} {} {}",
+        now.year(),
+        spl.next()?,
+        spl.next()?,
+        spl.next()?
+    );
+
+    let d = NaiveDateTime::parse_from_str(&date, "%Y %b %d %H:%M:%S").ok()?;
+    let mut d = local_to_utc(&d)?;
+
+    // the date is in the future - it must be from last year
+    if d > *now {
+        d = d.checked_sub_months(Months::new(12))?;
+    }
+
+    Some(d)
+}
+
+/// Parse an ISO8601 / RFC3339 timestamp at the start of a string.
+pub fn parse_iso8601_prefix(s: &str) -> Option<NaiveDateTime> {
+    let re = ISO8601_RE.get_or_init(|| {
+        Regex::new(
+            r"^\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+        )
+        .unwrap()
+    });
+    let m = re.find(s)?.as_str();
+
+    // explicit offset or UTC
+    if let Ok(d) = DateTime::parse_from_rfc3339(m) {
+        return Some(d.naive_utc());
+    }
+    if let Ok(d) = DateTime::parse_from_str(m, "%Y-%m-%dT%H:%M:%S%.f%z") {
+        return Some(d.naive_utc());
+    }
+
+    // no offset given - assume local time
+    let m = m.replacen(' ', "T", 1);
+    let d = NaiveDateTime::parse_from_str(&m, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    local_to_utc(&d)
+}
+
+/**
+ * Parse a time given on the command line (ie. `--since`) as either a
+ * duration relative to `now` or an absolute timestamp (local time unless an
+ * offset is given).
+ *
+ * Relative: `30s`, `5m`, `2h`, `1d`, `1w`, `1h30m`
+ * Absolute: `2023-10-09`, `2023-10-09 12:00`, `2023-10-09T12:00:00Z`, `12:00`
+ */
+pub fn parse_time_arg(now: &NaiveDateTime, s: &str) -> Result<NaiveDateTime> {
+    let s = s.trim();
+
+    // relative durations
+    let re = DURATION_RE.get_or_init(|| Regex::new(r"(\d+)([smhdw])").unwrap());
+    if !s.is_empty() && re.replace_all(s, "").is_empty() {
+        let mut secs: i64 = 0;
+        for caps in re.captures_iter(s) {
+            let n: i64 = caps[1]
+                .parse()
+                .with_context(|| format!("invalid duration: {}", s))?;
+            let mult = match &caps[2] {
+                "s" => 1,
+                "m" => 60,
+                "h" => 60 * 60,
+                "d" => 24 * 60 * 60,
+                _ => 7 * 24 * 60 * 60,
+            };
+            secs = n
+                .checked_mul(mult)
+                .and_then(|n| secs.checked_add(n))
+                .with_context(|| format!("duration too large: {}", s))?;
+        }
+
+        return Duration::try_seconds(secs)
+            .and_then(|d| now.checked_sub_signed(d))
+            .with_context(|| format!("invalid duration: {}", s));
+    }
+
+    // timestamps with an explicit offset
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Ok(d.naive_utc());
+    }
+
+    // local dates and times
+    let d = if let Ok(t) = NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+    {
+        let today = Local.from_utc_datetime(now).date_naive();
+        today.and_time(t)
+    } else if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        d.and_hms_opt(0, 0, 0).unwrap()
+    } else {
+        let s = s.replacen('T', " ", 1);
+        NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M"))
+            .with_context(|| format!("invalid time: {}", s))?
+    };
+
+    local_to_utc(&d).with_context(|| format!("invalid local time: {}", s))
+}
+
+/// Convert a local `NaiveDateTime` to UTC.
+pub fn local_to_utc(d: &NaiveDateTime) -> Option<NaiveDateTime> {
+    Local.from_local_datetime(d).earliest().map(|d| d.naive_utc())
+}
+
+/**
+ * Merge lines from multiple sources into a single list ordered by timestamp.
+ *
+ * Lines are never reordered within a source - the sources are interleaved
+ * the same way a merge sort would.  Lines without a timestamp (such as stack
+ * traces) inherit the timestamp of the line before them so they stay together.
+ *
+ * Returns `(source index, line)` pairs.
+ */
+pub fn merge_by_timestamp(
+    now: &NaiveDateTime,
+    sources: Vec<Vec<String>>,
+) -> Vec<(usize, String)> {
+    // tag every line with its (possibly inherited) timestamp
+    let mut sources: Vec<_> = sources
+        .into_iter()
+        .map(|lines| {
+            let mut last = None;
+            lines
+                .into_iter()
+                .map(|line| {
+                    last = parse_line_timestamp(now, &line).or(last);
+                    (last, line)
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .peekable()
+        })
+        .collect();
+
+    let mut out = vec![];
+    loop {
+        // find the source whose next line is the oldest
+        let next = sources
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, lines)| lines.peek().map(|(ts, _)| (*ts, i)))
+            .min();
+
+        match next {
+            Some((_, i)) => {
+                let (_, line) = sources[i].next().unwrap();
+                out.push((i, line));
+            }
+            None => break,
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_iso8601() {
+        let now = utc("2023-10-09 12:30:00");
+
+        let tests = [
+            ("2023-10-09T12:00:00Z foo", "2023-10-09 12:00:00"),
+            ("2023-10-09T12:00:00.123Z foo", "2023-10-09 12:00:00"),
+            ("2023-10-09T14:00:00+02:00 foo", "2023-10-09 12:00:00"),
+            ("2023-10-09T07:00:00-0500 foo", "2023-10-09 12:00:00"),
+            (
+                r#"{"v":0,"level":30,"msg":"hi","time":"2023-10-09T12:00:00Z"}"#,
+                "2023-10-09 12:00:00",
+            ),
+        ];
+
+        for (line, want) in tests {
+            let d = parse_line_timestamp(&now, line).unwrap();
+            assert_eq!(d.format("%Y-%m-%d %H:%M:%S").to_string(), want);
+        }
+
+        // no offset means local time
+        let d = parse_line_timestamp(&now, "2023-10-09 12:00:00 foo").unwrap();
+        assert_eq!(d, local_to_utc(&utc("2023-10-09 12:00:00")).unwrap());
+    }
+
+    #[test]
+    fn test_restarter() {
+        let now = local_to_utc(&utc("2023-10-09 12:30:00")).unwrap();
+
+        let tests = [
+            ("[ Oct  9 12:00:00 Executing start method (\"a\"). ]", "2023-10-09 12:00:00"),
+            ("[ Oct 9 12:00:00 Stopping because all processes in service exited. ]", "2023-10-09 12:00:00"),
+            // in the future, must be last year
+            ("[ Dec 25 08:00:00 Enabled. ]", "2022-12-25 08:00:00"),
+        ];
+
+        for (line, want) in tests {
+            let d = parse_line_timestamp(&now, line).unwrap();
+            assert_eq!(Local.from_utc_datetime(&d).naive_local(), utc(want));
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let now = utc("2023-10-09 12:30:00");
+
+        let a = [
+            "2023-10-09T12:00:01Z a1",
+            "  continued",
+            "2023-10-09T12:00:04Z a2",
+        ];
+        let b = [
+            "no timestamp",
+            "2023-10-09T12:00:02Z b1",
+            "2023-10-09T12:00:03Z b2",
+        ];
+        let sources = vec![
+            a.iter().map(|s| s.to_string()).collect(),
+            b.iter().map(|s| s.to_string()).collect(),
+        ];
+
+        let merged: Vec<_> = merge_by_timestamp(&now, sources)
+            .into_iter()
+            .map(|(i, line)| format!("{} {}", i, line))
+            .collect();
+
+        assert_eq!(
+            merged,
+            [
+                "1 no timestamp",
+                "0 2023-10-09T12:00:01Z a1",
+                "0   continued",
+                "1 2023-10-09T12:00:02Z b1",
+                "1 2023-10-09T12:00:03Z b2",
+                "0 2023-10-09T12:00:04Z a2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_time_arg() {
+        let now = utc("2023-10-09 12:30:00");
+
+        let relative = [
+            ("30s", "2023-10-09 12:29:30"),
+            ("5m", "2023-10-09 12:25:00"),
+            ("1h30m", "2023-10-09 11:00:00"),
+            ("2d", "2023-10-07 12:30:00"),
+            ("1w", "2023-10-02 12:30:00"),
+            ("2023-10-09T10:00:00Z", "2023-10-09 10:00:00"),
+            ("2023-10-09T12:00:00+02:00", "2023-10-09 10:00:00"),
+        ];
+        for (arg, want) in relative {
+            assert_eq!(
+                parse_time_arg(&now, arg).unwrap(),
+                utc(want),
+                "{}",
+                arg
+            );
+        }
+
+        let local = [
+            ("2023-10-08", "2023-10-08 00:00:00"),
+            ("2023-10-08 08:15", "2023-10-08 08:15:00"),
+            ("2023-10-08T08:15:30", "2023-10-08 08:15:30"),
+        ];
+        for (arg, want) in local {
+            let d = parse_time_arg(&now, arg).unwrap();
+            assert_eq!(Local.from_utc_datetime(&d).naive_local(), utc(want));
+        }
+
+        let d = parse_time_arg(&now, "08:15").unwrap();
+        let d = Local.from_utc_datetime(&d).naive_local();
+        assert_eq!(d.time(), NaiveTime::from_hms_opt(8, 15, 0).unwrap());
+
+        let invalid = [
+            "",
+            "5",
+            "5x",
+            "m5",
+            "yesterday",
+            "2023-13-01",
+            "99999999999999d",
+            "9999999999999999999s",
+        ];
+        for arg in invalid {
+            assert!(parse_time_arg(&now, arg).is_err(), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let now = utc("2023-10-09 12:30:00");
+
+        let invalid = [
+            "",
+            "hello world",
+            "[ foo bar baz ]",
+            "2023-10-09",
+            "2023-13-45T12:00:00Z",
+            "  2023-10-09T12:00:00Z",
+        ];
+
+        for line in invalid {
+            assert_eq!(parse_line_timestamp(&now, line), None, "{}", line);
+        }
+    }
+}